@@ -0,0 +1,123 @@
+# Settings
+settings = Einstellungen
+close = Schließen
+
+## Appearance
+appearance = Darstellung
+theme = Thema
+match-desktop = Wie Desktop
+dark = Dunkel
+light = Hell
+key-size = Tastengröße
+key-preview = Gedrückte Tasten vergrößern
+high-contrast = Hoher Kontrast
+high-contrast-description = Große fette Beschriftung auf schwarzen und weißen Tasten
+key-icons = Symbole auf Sondertasten
+key-icons-description = Symbole statt Namen auf Tasten wie Rücktaste, Umschalt und Medientasten anzeigen
+
+## Layout
+layout = Layout
+layout-full = Vollständig
+layout-compact = Kompakt
+layout-numpad = Ziffernblock
+numpad-auto = Ziffernblock für Zahlenfelder
+auto-show = Anzeigen, wenn ein Textfeld fokussiert ist
+
+## Switch access
+switch-access = Schaltersteuerung
+scan-mode = Scannen
+scan-off = Aus
+scan-auto = Automatisch
+scan-step = Schrittweise
+scan-method = Reihenfolge
+scan-row-column = Zeilen, dann Tasten
+scan-group = Gruppen, dann Tasten
+scan-interval = Scan-Intervall
+scan-select-key = Taste zum Auswählen
+scan-step-key = Taste zum Weiterschalten
+scan-key-description = Tastenname wie F12, wird nur gelesen, während ein Textfeld fokussiert ist. Ein Linksklick wählt ebenfalls aus und ein Rechtsklick schaltet weiter.
+dwell = Verweilklick
+dwell-description = Tasten durch Verweilen mit dem Zeiger drücken
+dwell-time = Verweildauer
+dwell-repeat = Rücktaste beim Verweilen wiederholen
+
+## Key labels
+label-backspace = Rück
+label-delete = Entf
+label-insert = Einfg
+label-home = Pos1
+label-end = Ende
+label-page-up = Bild↑
+label-page-down = Bild↓
+label-left = Links
+label-right = Rechts
+label-up = Auf
+label-down = Ab
+label-begin = Mitte
+label-enter = Eingabe
+label-tab = Tab
+label-escape = Esc
+label-caps-lock = Feststell
+label-num-lock = Num
+label-shift = Umschalt
+label-ctrl = Strg
+label-alt = Alt
+label-alt-gr = Alt Gr
+label-super = Super
+label-compose = Compose
+label-numpad = Num
+label-symbols = ?123
+label-letters = ABC
+
+## Key names read by screen readers
+key-space = Leertaste
+key-backspace = Rücktaste
+key-enter = Eingabe
+key-tab = Tabulator
+key-escape = Escape
+key-delete = Entfernen
+key-insert = Einfügen
+key-home = Pos1
+key-end = Ende
+key-page-up = Bild auf
+key-page-down = Bild ab
+key-left = Links
+key-right = Rechts
+key-up = Auf
+key-down = Ab
+key-shift = Umschalt
+key-ctrl = Steuerung
+key-alt = Alt
+key-super = Super
+key-caps-lock = Feststelltaste
+key-num-lock = Num-Taste
+key-emoji = Emoji
+key-numpad = Ziffernblock
+
+## Layers announced by screen readers
+layer-normal = Buchstaben
+layer-shift = Umschalt
+layer-symbols = Symbole
+
+## Typing
+typing = Eingabe
+suggestions = Wortvorschläge
+swipe-typing = Wischen zum Schreiben
+key-gestures = Gesten auf Leertaste und Rücktaste
+key-gestures-description = Leertaste ziehen, um den Cursor zu bewegen, und Rücktaste ziehen, um Wörter zu löschen
+autocorrect = Autokorrektur
+auto-capitalize = Satzanfänge großschreiben
+double-space-period = Doppeltes Leerzeichen fügt Punkt ein
+conversion = Eingabeumwandlung
+conversion-description = Getippte Buchstaben in eine andere Schrift umwandeln und aus Vorschlägen über den Tasten auswählen
+conversion-no-dictionary = Kein Wörterbuch gefunden, daher wird meist nur die Lesung angeboten. Umwandlungen können in { $path } ergänzt werden
+conversion-off = Aus
+conversion-pinyin = Chinesisch (Pinyin)
+conversion-kana = Japanisch (Romaji)
+conversion-hangul = Koreanisch (Hangul)
+external-ime = Andere Eingabemethode verwenden
+external-ime-description = Über eine Eingabemethode wie fcitx5 oder IBus schreiben und ihre Vorschläge über den Tasten anzeigen
+
+## Languages
+languages = Sprachen für Vorhersagen
+no-languages = Keine Wortlisten gefunden
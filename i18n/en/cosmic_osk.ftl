@@ -0,0 +1,123 @@
+# Settings
+settings = Settings
+close = Close
+
+## Appearance
+appearance = Appearance
+theme = Theme
+match-desktop = Match desktop
+dark = Dark
+light = Light
+key-size = Key size
+key-preview = Enlarge pressed keys
+high-contrast = High contrast
+high-contrast-description = Large bold labels on black and white keys
+key-icons = Icons on special keys
+key-icons-description = Show icons instead of names on keys such as Backspace, Shift and media keys
+
+## Layout
+layout = Layout
+layout-full = Full
+layout-compact = Compact
+layout-numpad = Numeric keypad
+numpad-auto = Numeric keypad for number fields
+auto-show = Show when a text field is focused
+
+## Switch access
+switch-access = Switch access
+scan-mode = Scanning
+scan-off = Off
+scan-auto = Automatic
+scan-step = Step
+scan-method = Scan order
+scan-row-column = Rows, then keys
+scan-group = Groups, then keys
+scan-interval = Scan interval
+scan-select-key = Select switch key
+scan-step-key = Step switch key
+scan-key-description = Key name such as F12, only read while a text field is focused. A left click also selects and a right click steps.
+dwell = Dwell click
+dwell-description = Press keys by resting the pointer on them
+dwell-time = Dwell time
+dwell-repeat = Repeat Backspace while resting on it
+
+## Key labels
+label-backspace = Bksp
+label-delete = Del
+label-insert = Insert
+label-home = Home
+label-end = End
+label-page-up = PgUp
+label-page-down = PgDn
+label-left = Left
+label-right = Right
+label-up = Up
+label-down = Down
+label-begin = Begin
+label-enter = Enter
+label-tab = Tab
+label-escape = Esc
+label-caps-lock = Caps
+label-num-lock = NumLk
+label-shift = Shift
+label-ctrl = Ctrl
+label-alt = Alt
+label-alt-gr = AltGr
+label-super = Super
+label-compose = Compose
+label-numpad = Num
+label-symbols = ?123
+label-letters = ABC
+
+## Key names read by screen readers
+key-space = Space
+key-backspace = Backspace
+key-enter = Enter
+key-tab = Tab
+key-escape = Escape
+key-delete = Delete
+key-insert = Insert
+key-home = Home
+key-end = End
+key-page-up = Page up
+key-page-down = Page down
+key-left = Left
+key-right = Right
+key-up = Up
+key-down = Down
+key-shift = Shift
+key-ctrl = Control
+key-alt = Alt
+key-super = Super
+key-caps-lock = Caps lock
+key-num-lock = Num lock
+key-emoji = Emoji
+key-numpad = Numeric keypad
+
+## Layers announced by screen readers
+layer-normal = Letters
+layer-shift = Shift
+layer-symbols = Symbols
+
+## Typing
+typing = Typing
+suggestions = Word suggestions
+swipe-typing = Swipe typing
+key-gestures = Space and Backspace gestures
+key-gestures-description = Drag the space bar to move the cursor and Backspace to delete words
+autocorrect = Autocorrect
+auto-capitalize = Capitalize sentences
+double-space-period = Double space inserts period
+conversion = Input conversion
+conversion-description = Compose typed letters into another script, choosing from candidates above the keys
+conversion-no-dictionary = No dictionary found, so mostly the reading is offered. Add conversions to { $path }
+conversion-off = Off
+conversion-pinyin = Chinese (pinyin)
+conversion-kana = Japanese (romaji)
+conversion-hangul = Korean (hangul)
+external-ime = Use another input method
+external-ime-description = Type through an input method such as fcitx5 or IBus, showing its candidates above the keys
+
+## Languages
+languages = Prediction languages
+no-languages = No word lists found
@@ -0,0 +1,83 @@
+#!/usr/bin/env python3
+# SPDX-License-Identifier: GPL-3.0-only
+"""Generate res/emoji/<language>.tsv from CLDR annotations.
+
+Usage: emoji-tables.py <cldr>/common [language...]
+
+Languages default to those in i18n/. Categories and skin tone support are
+kept from res/emoji/en.tsv, and emoji without annotations in a language use
+the English ones.
+"""
+
+import pathlib
+import sys
+import xml.etree.ElementTree as ET
+
+ROOT = pathlib.Path(__file__).resolve().parent.parent
+EMOJI_DIR = ROOT / "res" / "emoji"
+HEADER = [
+    "# Emoji table generated from CLDR annotations by scripts/emoji-tables.py",
+    "# category\temoji\ttones\tannotations separated by |",
+]
+
+
+def annotations(common, language):
+    """Name and keywords of each emoji, keyed without presentation selectors"""
+    result = {}
+    for folder in ("annotations", "annotationsDerived"):
+        path = common / folder / f"{language}.xml"
+        if not path.exists():
+            continue
+        for node in ET.parse(path).getroot().iter("annotation"):
+            text = (node.text or "").strip()
+            # Inherited from the parent locale
+            if not text or text == "↑↑↑":
+                continue
+            entry = result.setdefault(node.get("cp"), [None, []])
+            if node.get("type") == "tts":
+                entry[0] = text
+            else:
+                entry[1].extend(keyword.strip() for keyword in text.split("|"))
+    return result
+
+
+def annotation_field(entry):
+    name, keywords = entry
+    words = []
+    for word in [name, *keywords]:
+        if word and word not in words:
+            words.append(word)
+    return " | ".join(words)
+
+
+def main():
+    if len(sys.argv) < 2:
+        sys.exit(__doc__)
+    common = pathlib.Path(sys.argv[1])
+    languages = sys.argv[2:] or sorted(
+        path.name for path in (ROOT / "i18n").iterdir() if path.is_dir()
+    )
+
+    rows = []
+    for line in (EMOJI_DIR / "en.tsv").read_text().splitlines():
+        if line and not line.startswith("#"):
+            rows.append(line.split("\t"))
+
+    english = annotations(common, "en")
+    for language in languages:
+        local = annotations(common, language)
+        if not local:
+            print(f"no CLDR annotations for {language}", file=sys.stderr)
+            continue
+        lines = list(HEADER)
+        for category, emoji, tones, old in rows:
+            cp = emoji.replace("\ufe0f", "")
+            entry = local.get(cp) or english.get(cp)
+            field = annotation_field(entry) if entry else old
+            lines.append(f"{category}\t{emoji}\t{tones}\t{field}")
+        (EMOJI_DIR / f"{language}.tsv").write_text("\n".join(lines) + "\n")
+        print(f"wrote {language}.tsv")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use xkbcommon::xkb::Keysym;
+
+use crate::{
+    fl,
+    layout::{self, Action, Key, Layout},
+};
+
+/// Spoken name of a key that does not type a visible character
+pub fn keysym_name(keysym: Keysym) -> Option<String> {
+    let name = match keysym {
+        Keysym::space | Keysym::KP_Space => fl!("key-space"),
+        Keysym::BackSpace => fl!("key-backspace"),
+        Keysym::Return | Keysym::KP_Enter => fl!("key-enter"),
+        Keysym::Tab | Keysym::ISO_Left_Tab | Keysym::KP_Tab => fl!("key-tab"),
+        Keysym::Escape => fl!("key-escape"),
+        Keysym::Delete | Keysym::KP_Delete => fl!("key-delete"),
+        Keysym::Insert | Keysym::KP_Insert => fl!("key-insert"),
+        Keysym::Home | Keysym::KP_Home => fl!("key-home"),
+        Keysym::End | Keysym::KP_End => fl!("key-end"),
+        Keysym::Prior | Keysym::KP_Prior => fl!("key-page-up"),
+        Keysym::Next | Keysym::KP_Next => fl!("key-page-down"),
+        Keysym::Left | Keysym::KP_Left => fl!("key-left"),
+        Keysym::Right | Keysym::KP_Right => fl!("key-right"),
+        Keysym::Up | Keysym::KP_Up => fl!("key-up"),
+        Keysym::Down | Keysym::KP_Down => fl!("key-down"),
+        Keysym::Shift_L | Keysym::Shift_R => fl!("key-shift"),
+        Keysym::Control_L | Keysym::Control_R => fl!("key-ctrl"),
+        Keysym::Alt_L | Keysym::Alt_R | Keysym::ISO_Level3_Shift => fl!("key-alt"),
+        Keysym::Super_L | Keysym::Super_R => fl!("key-super"),
+        Keysym::Caps_Lock => fl!("key-caps-lock"),
+        Keysym::Num_Lock => fl!("key-num-lock"),
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Name of a layer, announced when it is switched to
+pub fn layer_name(layer: usize) -> String {
+    match layer {
+        layout::LAYER_SHIFT => fl!("layer-shift"),
+        layout::LAYER_SYMBOLS => fl!("layer-symbols"),
+        _ => fl!("layer-normal"),
+    }
+}
+
+/// Accessible name of a key, its label unless that is blank or abbreviated
+pub fn key_name(layout: &Layout, key: &Key) -> String {
+    match key.action {
+        Action::Keycode(kc) | Action::Shifted { keycode: kc, .. } => {
+            // Single characters are read as shown, so they follow the layer, and
+            // combining marks without the dotted circle they are shown on
+            let name = key.name.strip_prefix('\u{25CC}').unwrap_or(&key.name);
+            let mut chars = name.chars();
+            if let (Some(c), None) = (chars.next(), chars.next())
+                && !c.is_whitespace()
+            {
+                return name.to_string();
+            }
+            layout
+                .keysyms
+                .get(&kc)
+                .and_then(|&keysym| keysym_name(keysym))
+                .unwrap_or_else(|| key.name.clone())
+        }
+        Action::Layer(switch) => layer_name(switch.layer()),
+        Action::Numpad => fl!("key-numpad"),
+        Action::Emoji => fl!("key-emoji"),
+        Action::Settings => fl!("settings"),
+        Action::None => key.name.clone(),
+    }
+}
@@ -0,0 +1,174 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use cosmic::{
+    Theme,
+    cosmic_theme::Component,
+    iced::{Background, Color},
+    style, theme, widget,
+};
+
+use crate::layout::KeyClass;
+
+/// Opacity of the keyboard background, keys stay opaque
+const BACKGROUND_ALPHA: f32 = 0.85;
+
+/// Border width of keys in high contrast mode
+const HIGH_CONTRAST_BORDER: f32 = 3.0;
+
+/// Width of the ring around the focused key
+const FOCUS_RING: f32 = 2.0;
+
+/// Width of the ring around the focused key in high contrast mode
+const HIGH_CONTRAST_FOCUS_RING: f32 = 6.0;
+
+/// Ring around the focused key in high contrast mode, visible on black and white
+const HIGH_CONTRAST_FOCUS: Color = Color::from_rgb(1.0, 1.0, 0.0);
+
+/// Label size relative to the key size in high contrast mode
+pub const HIGH_CONTRAST_LABEL: f32 = 0.45;
+
+/// Number of steps the dwell ring fills in, limiting the number of cached images
+const DWELL_STEPS: f32 = 24.0;
+
+#[derive(Clone, Copy, Debug)]
+enum KeyState {
+    Active,
+    Hovered,
+    Pressed,
+    Disabled,
+}
+
+/// White on black keys, inverted when latched, pressed or for enter
+fn high_contrast_appearance(
+    class: KeyClass,
+    latched: bool,
+    focused: bool,
+    state: KeyState,
+) -> widget::button::Style {
+    let inverted = latched || class == KeyClass::Enter || matches!(state, KeyState::Pressed);
+    let (background, text) = if inverted {
+        (Color::WHITE, Color::BLACK)
+    } else {
+        (Color::BLACK, Color::WHITE)
+    };
+
+    let mut appearance = widget::button::Style::new();
+    appearance.background = Some(Background::Color(background));
+    appearance.text_color = Some(text);
+    appearance.icon_color = Some(text);
+    appearance.border_radius = 0.0.into();
+    appearance.border_width = HIGH_CONTRAST_BORDER;
+    appearance.border_color = Color::WHITE;
+    if matches!(state, KeyState::Disabled) {
+        appearance.text_color = Some(Color::from_rgb(0.6, 0.6, 0.6));
+    }
+    if focused || matches!(state, KeyState::Hovered) {
+        appearance.border_width = HIGH_CONTRAST_FOCUS_RING;
+        appearance.border_color = HIGH_CONTRAST_FOCUS;
+    }
+    appearance
+}
+
+fn key_appearance(
+    theme: &Theme,
+    class: KeyClass,
+    latched: bool,
+    focused: bool,
+    state: KeyState,
+) -> widget::button::Style {
+    let cosmic = theme.cosmic();
+    let component: &Component = if latched {
+        &cosmic.accent_button
+    } else {
+        match class {
+            KeyClass::Character => &cosmic.background.component,
+            KeyClass::Modifier => &cosmic.secondary.component,
+            KeyClass::Function => &cosmic.primary.component,
+            KeyClass::Enter => &cosmic.accent_button,
+        }
+    };
+    let (background, text) = match state {
+        KeyState::Active => (component.base, component.on),
+        KeyState::Hovered => (component.hover, component.on),
+        KeyState::Pressed => (component.pressed, component.on),
+        KeyState::Disabled => (component.disabled, component.on_disabled),
+    };
+
+    let mut appearance = widget::button::Style::new();
+    appearance.background = Some(Background::Color(background.into()));
+    appearance.text_color = Some(text.into());
+    appearance.icon_color = Some(text.into());
+    appearance.border_radius = cosmic.corner_radii.radius_s.into();
+    if focused {
+        appearance.border_width = FOCUS_RING;
+        appearance.border_color = cosmic.accent_color().into();
+    }
+    appearance
+}
+
+/// Key colored by its class, using the accent color when latched and a ring when scanned
+///
+/// High contrast keys ignore the theme so they stay readable with any desktop colors.
+pub fn key(class: KeyClass, latched: bool, high_contrast: bool, scanned: bool) -> style::Button {
+    let appearance = move |focused: bool, theme: &Theme, state: KeyState| {
+        let focused = focused || scanned;
+        if high_contrast {
+            high_contrast_appearance(class, latched, focused, state)
+        } else {
+            key_appearance(theme, class, latched, focused, state)
+        }
+    };
+    style::Button::Custom {
+        active: Box::new(move |focused, theme| appearance(focused, theme, KeyState::Active)),
+        disabled: Box::new(move |theme| appearance(false, theme, KeyState::Disabled)),
+        hovered: Box::new(move |focused, theme| appearance(focused, theme, KeyState::Hovered)),
+        pressed: Box::new(move |focused, theme| appearance(focused, theme, KeyState::Pressed)),
+    }
+}
+
+/// Ring filled clockwise from the top as dwell progresses from 0 to 1
+pub fn dwell_ring(progress: f32, high_contrast: bool) -> widget::icon::Handle {
+    let progress = (progress.clamp(0.0, 1.0) * DWELL_STEPS).round() / DWELL_STEPS;
+    let color = if high_contrast {
+        HIGH_CONTRAST_FOCUS
+    } else {
+        theme::active().cosmic().accent_color().into()
+    };
+    let [r, g, b, _] = color.into_rgba8();
+    let stroke = format!(
+        "fill=\"none\" stroke=\"#{r:02x}{g:02x}{b:02x}\" stroke-width=\"8\" stroke-linecap=\"round\""
+    );
+    let shape = if progress >= 1.0 {
+        format!("<circle cx=\"50\" cy=\"50\" r=\"40\" {stroke}/>")
+    } else {
+        let angle = progress * std::f32::consts::TAU;
+        let x = 50.0 + 40.0 * angle.sin();
+        let y = 50.0 - 40.0 * angle.cos();
+        let large_arc = u8::from(progress > 0.5);
+        format!("<path d=\"M 50 10 A 40 40 0 {large_arc} 1 {x:.2} {y:.2}\" {stroke}/>")
+    };
+    let svg =
+        format!("<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 100 100\">{shape}</svg>");
+    widget::icon::from_svg_bytes(svg.into_bytes())
+}
+
+/// Translucent keyboard background, opaque black in high contrast mode
+pub fn background(high_contrast: bool) -> style::Container {
+    style::Container::custom(move |theme| {
+        if high_contrast {
+            return widget::container::Style {
+                background: Some(Background::Color(Color::BLACK)),
+                text_color: Some(Color::WHITE),
+                ..Default::default()
+            };
+        }
+        let cosmic = theme.cosmic();
+        let mut background: Color = cosmic.background.base.into();
+        background.a *= BACKGROUND_ALPHA;
+        widget::container::Style {
+            background: Some(Background::Color(background)),
+            text_color: Some(cosmic.background.on.into()),
+            ..Default::default()
+        }
+    })
+}
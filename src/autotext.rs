@@ -0,0 +1,288 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use xkbcommon::xkb;
+
+use crate::predict::Model;
+
+/// Returns true if the next letter typed after this text starts a sentence
+pub fn sentence_start(before_cursor: &str) -> bool {
+    let trimmed = before_cursor.trim_end_matches([' ', '\t']);
+    match trimmed.chars().last() {
+        None | Some('\n') => true,
+        Some(c) => trimmed.len() != before_cursor.len() && matches!(c, '.' | '!' | '?'),
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    pub autocorrect: bool,
+    pub double_space_period: bool,
+}
+
+/// Text edit to apply through the input method, once the typed key that
+/// caused it shows in the text field
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Replacement {
+    /// Text before the cursor to delete
+    pub replaced: String,
+    pub text: String,
+}
+
+impl Replacement {
+    /// Bytes to delete before the cursor
+    pub fn before_length(&self) -> u32 {
+        self.replaced.len() as u32
+    }
+}
+
+/// Tracks typed keys to correct words and punctuation after they are typed
+#[derive(Debug, Default)]
+pub struct Autotext {
+    /// Word being typed
+    word: String,
+    /// Last two typed characters, most recent first
+    history: [Option<char>; 2],
+    /// Original word and correction with its separator, undone by backspace
+    correction: Option<(String, String)>,
+    /// Last word of the current sentence
+    previous: Option<String>,
+    /// Finished word and the word before it, not yet taken
+    completed: Option<(Option<String>, String)>,
+}
+
+impl Autotext {
+    /// Forget typed text, for example when focus changes
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Handle a typed keysym, returning an edit to apply if any
+    pub fn typed(
+        &mut self,
+        keysym: xkb::Keysym,
+        options: Options,
+        model: &Model,
+    ) -> Option<Replacement> {
+        if keysym == xkb::Keysym::BackSpace {
+            self.word.pop();
+            self.history = [None, None];
+            self.previous = None;
+            // Backspace removed the separator, restore the original word
+            let (original, corrected) = self.correction.take()?;
+            let corrected = corrected.trim_end_matches(|c: char| !c.is_alphanumeric());
+            self.word = original.clone();
+            // The user wants the original word, so it is worth learning
+            self.completed = Some((None, original.clone()));
+            return Some(Replacement {
+                replaced: corrected.to_string(),
+                text: original,
+            });
+        }
+
+        let Some(c) = keysym.key_char().filter(|c| !c.is_control()) else {
+            // Cursor movement and other keys end the word
+            self.reset();
+            return None;
+        };
+        let previous = self.history;
+        self.history = [Some(c), previous[0]];
+
+        if c.is_alphanumeric() || c == '\'' {
+            self.word.push(c);
+            self.correction = None;
+            return None;
+        }
+
+        let word = std::mem::take(&mut self.word);
+        let previous_word = self.previous.take();
+        if c.is_whitespace() && word.is_empty() {
+            self.previous = previous_word.clone();
+        }
+        if c == ' '
+            && options.double_space_period
+            && previous[0] == Some(' ')
+            && previous[1].is_some_and(char::is_alphanumeric)
+        {
+            self.correction = None;
+            return Some(Replacement {
+                replaced: "  ".to_string(),
+                text: ". ".to_string(),
+            });
+        }
+
+        if options.autocorrect
+            && !word.is_empty()
+            && let Some(corrected) = model.correction(&word)
+        {
+            let text = format!("{}{}", corrected, c);
+            let replacement = Replacement {
+                replaced: format!("{}{}", word, c),
+                text: text.clone(),
+            };
+            self.complete(previous_word, corrected.clone(), c);
+            self.correction = Some((word, text));
+            return Some(replacement);
+        }
+        self.correction = None;
+        if !word.is_empty() {
+            self.complete(previous_word, word, c);
+        }
+        None
+    }
+
+    fn complete(&mut self, previous: Option<String>, word: String, separator: char) {
+        if separator.is_whitespace() {
+            self.previous = Some(word.clone());
+        }
+        self.completed = Some((previous, word));
+    }
+
+    /// Take the last finished word with the word before it in the same sentence
+    pub fn take_completed(&mut self) -> Option<(Option<String>, String)> {
+        self.completed.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: Options = Options {
+        autocorrect: true,
+        double_space_period: true,
+    };
+    const NONE: Options = Options {
+        autocorrect: false,
+        double_space_period: false,
+    };
+
+    fn model() -> Model {
+        let mut model = Model::default();
+        model.parse("the 100\nhello 50\nworld 40\n");
+        model
+    }
+
+    /// Type text, returning the last replacement
+    fn type_text(
+        autotext: &mut Autotext,
+        text: &str,
+        options: Options,
+        model: &Model,
+    ) -> Option<Replacement> {
+        let mut replacement = None;
+        for c in text.chars() {
+            replacement = autotext.typed(xkb::utf32_to_keysym(c as u32), options, model);
+        }
+        replacement
+    }
+
+    fn replacement(replaced: &str, text: &str) -> Option<Replacement> {
+        Some(Replacement {
+            replaced: replaced.to_string(),
+            text: text.to_string(),
+        })
+    }
+
+    #[test]
+    fn sentence_start_at_field_start_and_after_punctuation() {
+        assert!(sentence_start(""));
+        assert!(sentence_start("Hello. "));
+        assert!(sentence_start("Really?  "));
+        assert!(sentence_start("Wow!\t"));
+        assert!(sentence_start("line\n"));
+        assert!(sentence_start("line\n  "));
+    }
+
+    #[test]
+    fn sentence_start_within_sentences() {
+        assert!(!sentence_start("Hello"));
+        assert!(!sentence_start("Hello "));
+        assert!(!sentence_start("Hello."));
+        assert!(!sentence_start("e.g."));
+        assert!(!sentence_start("Hi, "));
+    }
+
+    #[test]
+    fn autocorrect_and_undo_with_backspace() {
+        let model = model();
+        let mut autotext = Autotext::default();
+        assert_eq!(
+            type_text(&mut autotext, "teh ", ALL, &model),
+            replacement("teh ", "the ")
+        );
+        assert_eq!(autotext.take_completed(), Some((None, "the".to_string())));
+
+        // Backspace deletes the space, then the original word is restored
+        assert_eq!(
+            autotext.typed(xkb::Keysym::BackSpace, ALL, &model),
+            replacement("the", "teh")
+        );
+        assert_eq!(autotext.take_completed(), Some((None, "teh".to_string())));
+        assert_eq!(autotext.typed(xkb::Keysym::BackSpace, ALL, &model), None);
+    }
+
+    #[test]
+    fn undo_only_right_after_correction() {
+        let model = model();
+        let mut autotext = Autotext::default();
+        type_text(&mut autotext, "teh x", ALL, &model);
+        assert_eq!(autotext.typed(xkb::Keysym::BackSpace, ALL, &model), None);
+    }
+
+    #[test]
+    fn autocorrect_disabled() {
+        let model = model();
+        let mut autotext = Autotext::default();
+        assert_eq!(type_text(&mut autotext, "teh ", NONE, &model), None);
+        assert_eq!(autotext.take_completed(), Some((None, "teh".to_string())));
+        assert_eq!(autotext.typed(xkb::Keysym::BackSpace, NONE, &model), None);
+    }
+
+    #[test]
+    fn double_space_period() {
+        let model = model();
+        let mut autotext = Autotext::default();
+        assert_eq!(
+            type_text(&mut autotext, "hello  ", ALL, &model),
+            replacement("  ", ". ")
+        );
+        // A third space is left alone
+        assert_eq!(type_text(&mut autotext, " ", ALL, &model), None);
+    }
+
+    #[test]
+    fn double_space_period_only_after_words() {
+        let model = model();
+        let mut autotext = Autotext::default();
+        assert_eq!(type_text(&mut autotext, "hello.  ", ALL, &model), None);
+        assert_eq!(type_text(&mut autotext, "  ", ALL, &model), None);
+
+        let mut autotext = Autotext::default();
+        assert_eq!(type_text(&mut autotext, "hello  ", NONE, &model), None);
+    }
+
+    #[test]
+    fn completed_words_track_previous_word_in_sentence() {
+        let model = model();
+        let mut autotext = Autotext::default();
+        type_text(&mut autotext, "hello world ", NONE, &model);
+        assert_eq!(
+            autotext.take_completed(),
+            Some((Some("hello".to_string()), "world".to_string()))
+        );
+        assert_eq!(autotext.take_completed(), None);
+
+        type_text(&mut autotext, "end. go ", NONE, &model);
+        assert_eq!(autotext.take_completed(), Some((None, "go".to_string())));
+    }
+
+    #[test]
+    fn other_keys_reset() {
+        let model = model();
+        let mut autotext = Autotext::default();
+        type_text(&mut autotext, "hello", NONE, &model);
+        autotext.typed(xkb::Keysym::Left, NONE, &model);
+        type_text(&mut autotext, "world ", NONE, &model);
+        assert_eq!(autotext.take_completed(), Some((None, "world".to_string())));
+    }
+}
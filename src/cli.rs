@@ -0,0 +1,254 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use crate::layout::LayoutKind;
+
+pub const USAGE: &str = "\
+Usage: cosmic-osk [COMMAND] [OPTIONS]
+
+Commands, forwarded to the running keyboard if there is one:
+  show                       Show the keyboard
+  hide                       Hide the keyboard
+  toggle                     Show the keyboard if hidden, otherwise hide it
+
+Options:
+  --layout <name>            Use the full, compact or numpad layout
+  --seat <name>              Type on the seat with this name
+  --output <connector>       Show the keyboard on this output, such as eDP-1
+  --windowed                 Show the keyboard in a normal window
+  --hidden                   Start without showing the keyboard
+  --settings                 Open the settings window
+  --list-layouts             List layout names and exit
+  --import-dictionary <file> Add words from a plain text file to the user dictionary
+  --export-dictionary <file> Write the user dictionary to a plain text file
+  --debug-dump-keymap        Print each keymap as it is received
+  -h, --help                 Show this help";
+
+/// Request to a running keyboard, sent as one line of text
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Request {
+    Show,
+    Hide,
+    Toggle,
+    Layout(LayoutKind),
+    Settings,
+}
+
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Show => write!(f, "show"),
+            Self::Hide => write!(f, "hide"),
+            Self::Toggle => write!(f, "toggle"),
+            Self::Layout(kind) => write!(f, "layout {}", kind.name()),
+            Self::Settings => write!(f, "settings"),
+        }
+    }
+}
+
+impl FromStr for Request {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_whitespace().collect::<Vec<_>>()[..] {
+            ["show"] => Ok(Self::Show),
+            ["hide"] => Ok(Self::Hide),
+            ["toggle"] => Ok(Self::Toggle),
+            ["settings"] => Ok(Self::Settings),
+            ["layout", name] => LayoutKind::from_name(name)
+                .map(Self::Layout)
+                .ok_or_else(|| format!("unknown layout {:?}", name)),
+            _ => Err(format!("invalid request {:?}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Args {
+    pub command: Option<Request>,
+    pub layout: Option<LayoutKind>,
+    pub seat: Option<String>,
+    pub output: Option<String>,
+    pub windowed: bool,
+    pub hidden: bool,
+    pub settings: bool,
+    pub list_layouts: bool,
+    pub import_dictionary: Option<PathBuf>,
+    pub export_dictionary: Option<PathBuf>,
+    pub dump_keymap: bool,
+    pub help: bool,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {}", name))
+            };
+            match arg.as_str() {
+                "show" | "hide" | "toggle" => {
+                    if parsed.command.is_some() {
+                        return Err(format!("unexpected command {:?}", arg));
+                    }
+                    parsed.command = Some(arg.parse()?);
+                }
+                "--layout" => {
+                    let name = value(&arg)?;
+                    parsed.layout = Some(
+                        LayoutKind::from_name(&name)
+                            .ok_or_else(|| format!("unknown layout {:?}", name))?,
+                    );
+                }
+                "--seat" => parsed.seat = Some(value(&arg)?),
+                "--output" => parsed.output = Some(value(&arg)?),
+                "--windowed" => parsed.windowed = true,
+                "--hidden" => parsed.hidden = true,
+                "--settings" => parsed.settings = true,
+                "--list-layouts" => parsed.list_layouts = true,
+                "--import-dictionary" => parsed.import_dictionary = Some(value(&arg)?.into()),
+                "--export-dictionary" => parsed.export_dictionary = Some(value(&arg)?.into()),
+                "--debug-dump-keymap" => parsed.dump_keymap = true,
+                "-h" | "--help" => parsed.help = true,
+                _ => return Err(format!("unknown argument {:?}", arg)),
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Requests to forward to a running keyboard, which is shown if no
+    /// command was given and it was not started hidden or for settings
+    pub fn requests(&self) -> Vec<Request> {
+        let mut requests = Vec::new();
+        if let Some(layout) = self.layout {
+            requests.push(Request::Layout(layout));
+        }
+        match self.command {
+            Some(command) => requests.push(command),
+            None if !self.hidden && !self.settings => requests.push(Request::Show),
+            None => {}
+        }
+        if self.settings {
+            requests.push(Request::Settings);
+        }
+        requests
+    }
+
+    /// Options that only apply when starting the keyboard
+    pub fn has_startup_options(&self) -> bool {
+        self.seat.is_some()
+            || self.output.is_some()
+            || self.windowed
+            || self.hidden
+            || self.dump_keymap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn request_round_trip() {
+        let mut requests = vec![
+            Request::Show,
+            Request::Hide,
+            Request::Toggle,
+            Request::Settings,
+        ];
+        requests.extend(LayoutKind::ALL.iter().copied().map(Request::Layout));
+        for request in requests {
+            assert_eq!(request.to_string().parse(), Ok(request));
+        }
+    }
+
+    #[test]
+    fn request_parse_errors() {
+        assert!("".parse::<Request>().is_err());
+        assert!("shw".parse::<Request>().is_err());
+        assert!("show now".parse::<Request>().is_err());
+        assert!("layout".parse::<Request>().is_err());
+        assert_eq!(
+            "layout qwerty".parse::<Request>(),
+            Err("unknown layout \"qwerty\"".to_string())
+        );
+        assert_eq!(" toggle \n".parse(), Ok(Request::Toggle));
+    }
+
+    #[test]
+    fn parse_no_args() {
+        let args = parse(&[]).unwrap();
+        assert_eq!(args.command, None);
+        assert_eq!(args.requests(), [Request::Show]);
+        assert!(!args.has_startup_options());
+    }
+
+    #[test]
+    fn parse_options() {
+        let args = parse(&[
+            "toggle",
+            "--layout",
+            "compact",
+            "--seat",
+            "seat0",
+            "--output",
+            "eDP-1",
+            "--windowed",
+            "--import-dictionary",
+            "words.txt",
+        ])
+        .unwrap();
+        assert_eq!(args.command, Some(Request::Toggle));
+        assert_eq!(args.layout, Some(LayoutKind::Compact));
+        assert_eq!(args.seat.as_deref(), Some("seat0"));
+        assert_eq!(args.output.as_deref(), Some("eDP-1"));
+        assert!(args.windowed);
+        assert_eq!(args.import_dictionary, Some(PathBuf::from("words.txt")));
+        assert_eq!(
+            args.requests(),
+            [Request::Layout(LayoutKind::Compact), Request::Toggle]
+        );
+        assert!(args.has_startup_options());
+    }
+
+    #[test]
+    fn parse_hidden_and_settings_requests() {
+        assert_eq!(parse(&["--hidden"]).unwrap().requests(), []);
+        assert_eq!(
+            parse(&["--settings"]).unwrap().requests(),
+            [Request::Settings]
+        );
+        assert_eq!(
+            parse(&["show", "--settings"]).unwrap().requests(),
+            [Request::Show, Request::Settings]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            parse(&["--frobnicate"]).unwrap_err(),
+            "unknown argument \"--frobnicate\""
+        );
+        assert_eq!(
+            parse(&["--layout", "qwerty"]).unwrap_err(),
+            "unknown layout \"qwerty\""
+        );
+        assert_eq!(
+            parse(&["--layout"]).unwrap_err(),
+            "missing value for --layout"
+        );
+        assert_eq!(
+            parse(&["show", "hide"]).unwrap_err(),
+            "unexpected command \"hide\""
+        );
+        assert!(parse(&["settings"]).is_err());
+    }
+}
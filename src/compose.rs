@@ -0,0 +1,203 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{collections::HashMap, ffi::OsString};
+use xkbcommon::xkb::{self, compose};
+
+use crate::layout::{self, Layout};
+
+/// Locale of the compose table, from the same variables as the C library
+fn locale() -> OsString {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|name| std::env::var_os(name).filter(|value| !value.is_empty()))
+        .unwrap_or_else(|| "C".into())
+}
+
+/// Returns true for dead keys and the compose key, which start or continue a
+/// sequence without typing anything
+fn is_dead(keysym: xkb::Keysym) -> bool {
+    keysym == xkb::Keysym::Multi_key
+        || (xkb::Keysym::dead_grave.raw()..=xkb::Keysym::dead_longsolidusoverlay.raw())
+            .contains(&keysym.raw())
+}
+
+/// Follows dead keys and compose sequences, which the focused application
+/// composes with the compose table of the same locale
+#[derive(Default)]
+pub struct Compose {
+    table: Option<compose::Table>,
+    state: Option<compose::State>,
+    /// Keysyms typed in the pending sequence
+    sequence: Vec<xkb::Keysym>,
+    /// Text composed by finishing the sequence, by key label
+    preview: HashMap<String, String>,
+}
+
+impl Compose {
+    pub fn new() -> Self {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let locale = locale();
+        match compose::Table::new_from_locale(&context, &locale, compose::COMPILE_NO_FLAGS) {
+            Ok(table) => Self::with_table(table),
+            Err(()) => {
+                log::warn!("no compose table for locale {:?}", locale);
+                Self::default()
+            }
+        }
+    }
+
+    fn with_table(table: compose::Table) -> Self {
+        Self {
+            state: Some(compose::State::new(&table, compose::STATE_NO_FLAGS)),
+            table: Some(table),
+            ..Default::default()
+        }
+    }
+
+    /// Forget the pending sequence, for example when focus changes
+    pub fn reset(&mut self) {
+        if let Some(state) = &mut self.state {
+            state.reset();
+        }
+        self.sequence.clear();
+        self.preview.clear();
+    }
+
+    /// Handle a typed keysym, previewing what the keys of the layouts would compose
+    pub fn typed(&mut self, keysym: xkb::Keysym, layouts: &[Layout]) {
+        let (Some(table), Some(state)) = (&self.table, &mut self.state) else {
+            return;
+        };
+        // Modifiers do not change the sequence
+        if state.feed(keysym) == compose::FeedResult::Ignored {
+            return;
+        }
+        if state.status() != compose::Status::Composing {
+            self.sequence.clear();
+            self.preview.clear();
+            return;
+        }
+        self.sequence.push(keysym);
+
+        self.preview.clear();
+        let labels = layouts
+            .iter()
+            .flat_map(|layout| layout.layers.iter())
+            .flat_map(|layer| layer.rows.iter().flatten())
+            .map(|key| key.name.as_str());
+        for label in labels {
+            let mut chars = label.chars();
+            let (Some(c), None) = (chars.next(), chars.next()) else {
+                continue;
+            };
+            if self.preview.contains_key(label) {
+                continue;
+            }
+            let mut preview = compose::State::new(table, compose::STATE_NO_FLAGS);
+            for &keysym in self.sequence.iter() {
+                preview.feed(keysym);
+            }
+            preview.feed(xkb::utf32_to_keysym(c as u32));
+            if preview.status() == compose::Status::Composed
+                && let Some(text) = preview.utf8()
+            {
+                self.preview.insert(label.to_string(), text);
+            }
+        }
+    }
+
+    /// Returns true if the dead key or compose key with this label was typed
+    /// in the pending sequence. Other keys in it are typed as usual.
+    pub fn is_pending(&self, label: &str) -> bool {
+        self.sequence
+            .iter()
+            .any(|&keysym| is_dead(keysym) && layout::key_label(keysym) == label)
+    }
+
+    /// Text typed by the key with this label, when it finishes the pending sequence
+    pub fn preview(&self, label: &str) -> Option<&str> {
+        self.preview.get(label).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::LayoutKind;
+    use xkb::Keysym;
+
+    const TABLE: &str = r#"
+<Multi_key> <a> <a> : "å"
+<Multi_key> <a> <e> : "æ"
+<dead_acute> <e> : "é"
+"#;
+
+    fn compose() -> Compose {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let table = compose::Table::new_from_buffer(
+            &context,
+            TABLE,
+            "C",
+            compose::FORMAT_TEXT_V1,
+            compose::COMPILE_NO_FLAGS,
+        )
+        .unwrap();
+        Compose::with_table(table)
+    }
+
+    fn layouts() -> Vec<Layout> {
+        // Labels use the fallback language
+        crate::localize::localizer();
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(
+            &context,
+            "evdev",
+            "pc105",
+            "us",
+            "",
+            None,
+            xkb::COMPILE_NO_FLAGS,
+        )
+        .unwrap();
+        vec![LayoutKind::Full.layout(&keymap)]
+    }
+
+    #[test]
+    fn compose_key_is_pending_but_not_letters() {
+        let layouts = layouts();
+        let mut compose = compose();
+        compose.typed(Keysym::Multi_key, &layouts);
+        compose.typed(Keysym::a, &layouts);
+        assert!(compose.is_pending(&layout::key_label(Keysym::Multi_key)));
+        assert!(!compose.is_pending("a"));
+        assert_eq!(compose.preview("a"), Some("å"));
+        assert_eq!(compose.preview("e"), Some("æ"));
+        assert_eq!(compose.preview("o"), None);
+    }
+
+    #[test]
+    fn dead_key_previews_and_finishes() {
+        let layouts = layouts();
+        let mut compose = compose();
+        compose.typed(Keysym::dead_acute, &layouts);
+        assert!(compose.is_pending(&layout::key_label(Keysym::dead_acute)));
+        assert_eq!(compose.preview("e"), Some("é"));
+
+        compose.typed(Keysym::e, &layouts);
+        assert!(!compose.is_pending(&layout::key_label(Keysym::dead_acute)));
+        assert_eq!(compose.preview("e"), None);
+    }
+
+    #[test]
+    fn modifiers_and_reset() {
+        let layouts = layouts();
+        let mut compose = compose();
+        compose.typed(Keysym::dead_acute, &layouts);
+        compose.typed(Keysym::Shift_L, &layouts);
+        assert_eq!(compose.preview("e"), Some("é"));
+
+        compose.reset();
+        assert!(!compose.is_pending(&layout::key_label(Keysym::dead_acute)));
+        assert_eq!(compose.preview("e"), None);
+    }
+}
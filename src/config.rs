@@ -5,9 +5,87 @@ use cosmic::{
     theme,
 };
 use serde::{Deserialize, Serialize};
+use std::{env, fs, path::PathBuf};
+
+use crate::{
+    convert::Conversion,
+    layout::LayoutKind,
+    scan::{ScanMethod, ScanMode},
+};
 
 pub const CONFIG_VERSION: u64 = 1;
 
+/// App ID that config was saved under by earlier versions
+pub const OLD_APP_ID: &str = "com.system76.CosmicEdit";
+
+/// Keys of [`Config`], which earlier versions saved under the old app ID
+const MIGRATE_KEYS: &[&str] = &[
+    "app_theme",
+    "layout",
+    "numpad_docked",
+    "numpad_auto",
+    "emoji_recent",
+    "suggestions",
+    "languages",
+    "auto_capitalize",
+    "double_space_period",
+    "autocorrect",
+    "swipe_typing",
+    "key_gestures",
+    "conversion",
+    "external_ime",
+    "key_size",
+    "key_preview",
+    "auto_show",
+    "high_contrast",
+    "key_icons",
+    "scan_mode",
+    "scan_method",
+    "scan_interval",
+    "scan_select_key",
+    "scan_step_key",
+    "dwell",
+    "dwell_time",
+    "dwell_repeat",
+];
+
+fn config_dir(app_id: &str) -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|config_home| !config_home.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(
+        config_home
+            .join("cosmic")
+            .join(app_id)
+            .join(format!("v{}", CONFIG_VERSION)),
+    )
+}
+
+/// Import config saved under the old app ID, if there is no config yet
+pub fn migrate(app_id: &str) {
+    let (Some(old_dir), Some(new_dir)) = (config_dir(OLD_APP_ID), config_dir(app_id)) else {
+        return;
+    };
+    if new_dir.exists() || !old_dir.is_dir() {
+        return;
+    }
+    if let Err(err) = fs::create_dir_all(&new_dir) {
+        log::warn!("failed to create {}: {}", new_dir.display(), err);
+        return;
+    }
+    for key in MIGRATE_KEYS.iter() {
+        let old_path = old_dir.join(key);
+        if !old_path.is_file() {
+            continue;
+        }
+        match fs::copy(&old_path, new_dir.join(key)) {
+            Ok(_) => log::info!("migrated config {} from {}", key, OLD_APP_ID),
+            Err(err) => log::warn!("failed to migrate config {}: {}", key, err),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum AppTheme {
     Dark,
@@ -36,12 +114,91 @@ impl AppTheme {
 #[derive(Clone, CosmicConfigEntry, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Config {
     pub app_theme: AppTheme,
+    pub layout: LayoutKind,
+    pub numpad_docked: bool,
+    pub numpad_auto: bool,
+    pub emoji_recent: Vec<String>,
+    pub suggestions: bool,
+    /// Languages used for word prediction, the interface language if empty
+    pub languages: Vec<String>,
+    pub auto_capitalize: bool,
+    pub double_space_period: bool,
+    pub autocorrect: bool,
+    pub swipe_typing: bool,
+    /// Drag the space bar to move the cursor and Backspace to delete words
+    pub key_gestures: bool,
+    /// Engine composing typed letters into another script, such as pinyin into Chinese
+    pub conversion: Conversion,
+    /// Leave the input method to another IME such as fcitx5 or IBus and only
+    /// type with the virtual keyboard
+    pub external_ime: bool,
+    /// Height of a key in logical pixels
+    pub key_size: u16,
+    /// Show pressed keys with a larger label
+    pub key_preview: bool,
+    /// Show the keyboard when a text field is focused and hide it afterwards
+    pub auto_show: bool,
+    /// Bold oversized labels on maximum contrast keys, regardless of theme
+    pub high_contrast: bool,
+    /// Show icons instead of names on special keys such as Backspace
+    pub key_icons: bool,
+    /// Highlight keys in turn for switch access instead of pressing them directly
+    pub scan_mode: ScanMode,
+    pub scan_method: ScanMethod,
+    /// Time the highlight stays on a group or key in automatic scanning, in milliseconds
+    pub scan_interval: u32,
+    /// Name of the keysym selecting the highlighted keys, such as "F12", none if empty
+    pub scan_select_key: String,
+    /// Name of the keysym advancing the highlight, none if empty
+    pub scan_step_key: String,
+    /// Press keys by resting the pointer on them
+    pub dwell: bool,
+    /// Time the pointer rests on a key before it is pressed, in milliseconds
+    pub dwell_time: u32,
+    /// Press Backspace again while the pointer keeps resting on it
+    pub dwell_repeat: bool,
+}
+
+impl Config {
+    pub fn languages(&self) -> Vec<String> {
+        if self.languages.is_empty() {
+            vec![crate::localize::language()]
+        } else {
+            self.languages.clone()
+        }
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             app_theme: AppTheme::System,
+            layout: LayoutKind::Full,
+            numpad_docked: false,
+            numpad_auto: true,
+            emoji_recent: Vec::new(),
+            suggestions: true,
+            languages: Vec::new(),
+            auto_capitalize: false,
+            double_space_period: false,
+            autocorrect: false,
+            swipe_typing: false,
+            key_gestures: true,
+            conversion: Conversion::Off,
+            external_ime: false,
+            key_size: 64,
+            key_preview: true,
+            auto_show: false,
+            high_contrast: false,
+            key_icons: true,
+            scan_mode: ScanMode::Off,
+            scan_method: ScanMethod::RowColumn,
+            scan_interval: 1000,
+            scan_select_key: String::new(),
+            scan_step_key: String::new(),
+            dwell: false,
+            dwell_time: 1000,
+            dwell_repeat: true,
         }
     }
 }
@@ -0,0 +1,935 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::predict;
+
+/// Engine converting typed letters into another script
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Conversion {
+    #[default]
+    Off,
+    /// Chinese from pinyin
+    Pinyin,
+    /// Japanese kana and kanji from romaji
+    Kana,
+    /// Korean syllables from jamo, or QWERTY letters in two set positions
+    Hangul,
+}
+
+impl Conversion {
+    pub const ALL: &'static [Self] = &[Self::Off, Self::Pinyin, Self::Kana, Self::Hangul];
+
+    /// Name of the dictionary files of the engine
+    fn dictionary_name(&self) -> Option<&'static str> {
+        match self {
+            Self::Off => None,
+            Self::Pinyin => Some("pinyin"),
+            Self::Kana => Some("kana"),
+            Self::Hangul => Some("hangul"),
+        }
+    }
+
+    /// Create the engine, loading its dictionary
+    pub fn engine(&self) -> Option<Box<dyn Engine>> {
+        let dictionary = Dictionary::load(self.dictionary_name()?);
+        match self {
+            Self::Off => None,
+            Self::Pinyin => Some(Box::new(Pinyin::new(dictionary))),
+            Self::Kana => Some(Box::new(Kana::new(dictionary))),
+            Self::Hangul => Some(Box::new(Hangul::new(dictionary))),
+        }
+    }
+
+    /// Path where a dictionary can be added, if the engine has none
+    pub fn missing_dictionary(&self) -> Option<PathBuf> {
+        let paths = dictionary_paths(self.dictionary_name()?);
+        if paths.iter().any(|path| path.is_file()) {
+            return None;
+        }
+        paths.into_iter().next()
+    }
+}
+
+/// Dictionary files in the XDG data directories, most important first
+fn dictionary_paths(name: &str) -> Vec<PathBuf> {
+    predict::data_dirs()
+        .iter()
+        .map(|data_dir| {
+            data_dir
+                .join("cosmic-osk/conversion")
+                .join(format!("{}.txt", name))
+        })
+        .collect()
+}
+
+/// Conversion of the start of the composition
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Candidate {
+    pub text: String,
+    /// Characters of the reading replaced by the text
+    pub length: usize,
+}
+
+/// Composes typed characters into a reading shown as preedit text, and
+/// converts it into candidates
+pub trait Engine {
+    /// Add a typed character, returning false if it cannot be composed
+    fn push(&mut self, c: char) -> bool;
+
+    /// Remove the last typed character, returning false if nothing is composed
+    fn pop(&mut self) -> bool;
+
+    /// Text shown in the text field while composing
+    fn preedit(&self) -> String;
+
+    /// Conversions of the reading, best first
+    fn candidates(&self, limit: usize) -> Vec<Candidate>;
+
+    /// Remove the part of the reading converted by a committed candidate
+    fn consume(&mut self, candidate: &Candidate);
+
+    fn reset(&mut self);
+
+    fn is_empty(&self) -> bool {
+        self.preedit().is_empty()
+    }
+
+    /// Space commits the best candidate instead of typing a space
+    fn space_selects(&self) -> bool {
+        true
+    }
+}
+
+/// Conversions by reading, loaded from `cosmic-osk/conversion/<engine>.txt`
+/// in the XDG data directories. Each line holds a reading, its conversion
+/// and a count, for example `nihao 你好 5000` or `かんじ 漢字 800`. Small
+/// starter dictionaries are installed from `res/conversion`, files in the
+/// user data directory add to them.
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    entries: BTreeMap<String, Vec<(String, u64)>>,
+}
+
+impl Dictionary {
+    pub fn load(name: &str) -> Self {
+        let mut dictionary = Self::default();
+        for path in dictionary_paths(name).iter().rev() {
+            match dictionary.load_file(path) {
+                Ok(()) => log::info!("loaded conversions from {}", path.display()),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    log::warn!(
+                        "failed to load conversions from {}: {}",
+                        path.display(),
+                        err
+                    )
+                }
+            }
+        }
+        dictionary
+    }
+
+    pub fn load_file(&mut self, path: &Path) -> io::Result<()> {
+        let data = fs::read_to_string(path)?;
+        self.parse(&data);
+        Ok(())
+    }
+
+    pub fn parse(&mut self, data: &str) {
+        for line in data.lines() {
+            if line.starts_with('#') {
+                continue;
+            }
+            let [reading, text, count] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+                continue;
+            };
+            let Ok(count) = count.parse::<u64>() else {
+                continue;
+            };
+            let conversions = self.entries.entry(reading.to_string()).or_default();
+            match conversions.iter_mut().find(|(known, _)| known == text) {
+                Some((_, known_count)) => *known_count += count,
+                None => conversions.push((text.to_string(), count)),
+            }
+        }
+    }
+
+    /// Conversions of the whole reading, then of longer readings starting with it,
+    /// then of the longest readings the reading starts with
+    pub fn convert(&self, reading: &str, limit: usize) -> Vec<Candidate> {
+        let length = reading.chars().count();
+        let mut candidates: Vec<Candidate> = Vec::new();
+        let mut add = |conversions: &[(String, u64)], length: usize| {
+            let mut conversions: Vec<&(String, u64)> = conversions.iter().collect();
+            conversions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            for (text, _) in conversions {
+                if !candidates.iter().any(|candidate| &candidate.text == text) {
+                    candidates.push(Candidate {
+                        text: text.clone(),
+                        length,
+                    });
+                }
+            }
+        };
+
+        if let Some(conversions) = self.entries.get(reading) {
+            add(conversions, length);
+        }
+        let completions: Vec<(String, u64)> = self
+            .entries
+            .range(reading.to_string()..)
+            .skip_while(|(known, _)| known.as_str() == reading)
+            .take_while(|(known, _)| known.starts_with(reading))
+            .flat_map(|(_, conversions)| conversions.iter().cloned())
+            .collect();
+        add(&completions, length);
+        for prefix_length in (1..length).rev() {
+            let end = reading
+                .char_indices()
+                .nth(prefix_length)
+                .map_or(reading.len(), |(index, _)| index);
+            if let Some(conversions) = self.entries.get(&reading[..end]) {
+                add(conversions, prefix_length);
+            }
+        }
+        candidates.truncate(limit);
+        candidates
+    }
+}
+
+/// Remove the first characters of a string
+fn remove_chars(text: &mut String, count: usize) {
+    let end = text
+        .char_indices()
+        .nth(count)
+        .map_or(text.len(), |(index, _)| index);
+    text.drain(..end);
+}
+
+/// Chinese characters from pinyin letters without tones, `v` standing for `ü`
+pub struct Pinyin {
+    dictionary: Dictionary,
+    reading: String,
+}
+
+impl Pinyin {
+    pub fn new(dictionary: Dictionary) -> Self {
+        Self {
+            dictionary,
+            reading: String::new(),
+        }
+    }
+}
+
+impl Engine for Pinyin {
+    fn push(&mut self, c: char) -> bool {
+        if c.is_ascii_lowercase() {
+            self.reading.push(c);
+            return true;
+        }
+        // An apostrophe separates syllables but is not part of the reading
+        c == '\'' && !self.reading.is_empty()
+    }
+
+    fn pop(&mut self) -> bool {
+        self.reading.pop().is_some()
+    }
+
+    fn preedit(&self) -> String {
+        self.reading.clone()
+    }
+
+    fn candidates(&self, limit: usize) -> Vec<Candidate> {
+        self.dictionary.convert(&self.reading, limit)
+    }
+
+    fn consume(&mut self, candidate: &Candidate) {
+        remove_chars(&mut self.reading, candidate.length);
+    }
+
+    fn reset(&mut self) {
+        self.reading.clear();
+    }
+}
+
+/// Hiragana by romaji, with rows of consonants followed by the vowels a, i, u, e and o
+const KANA_ROWS: &[(&str, [&str; 5])] = &[
+    ("", ["あ", "い", "う", "え", "お"]),
+    ("k", ["か", "き", "く", "け", "こ"]),
+    ("g", ["が", "ぎ", "ぐ", "げ", "ご"]),
+    ("s", ["さ", "し", "す", "せ", "そ"]),
+    ("z", ["ざ", "じ", "ず", "ぜ", "ぞ"]),
+    ("t", ["た", "ち", "つ", "て", "と"]),
+    ("d", ["だ", "ぢ", "づ", "で", "ど"]),
+    ("n", ["な", "に", "ぬ", "ね", "の"]),
+    ("h", ["は", "ひ", "ふ", "へ", "ほ"]),
+    ("b", ["ば", "び", "ぶ", "べ", "ぼ"]),
+    ("p", ["ぱ", "ぴ", "ぷ", "ぺ", "ぽ"]),
+    ("m", ["ま", "み", "む", "め", "も"]),
+    ("y", ["や", "い", "ゆ", "いぇ", "よ"]),
+    ("r", ["ら", "り", "る", "れ", "ろ"]),
+    ("w", ["わ", "うぃ", "う", "うぇ", "を"]),
+    ("f", ["ふぁ", "ふぃ", "ふ", "ふぇ", "ふぉ"]),
+    ("v", ["ゔぁ", "ゔぃ", "ゔ", "ゔぇ", "ゔぉ"]),
+    ("x", ["ぁ", "ぃ", "ぅ", "ぇ", "ぉ"]),
+    ("l", ["ぁ", "ぃ", "ぅ", "ぇ", "ぉ"]),
+];
+
+/// Contracted sounds, a kana of the i column followed by a small ya, yu, ye or yo
+const KANA_CONTRACTED: &[(&str, &str)] = &[
+    ("ky", "き"),
+    ("gy", "ぎ"),
+    ("sy", "し"),
+    ("sh", "し"),
+    ("zy", "じ"),
+    ("j", "じ"),
+    ("jy", "じ"),
+    ("ty", "ち"),
+    ("cy", "ち"),
+    ("ch", "ち"),
+    ("dy", "ぢ"),
+    ("ny", "に"),
+    ("hy", "ひ"),
+    ("by", "び"),
+    ("py", "ぴ"),
+    ("my", "み"),
+    ("ry", "り"),
+];
+
+/// Romaji that do not follow the rows
+const KANA_SPECIAL: &[(&str, &str)] = &[
+    ("shi", "し"),
+    ("chi", "ち"),
+    ("tsu", "つ"),
+    ("ji", "じ"),
+    ("fu", "ふ"),
+    ("nn", "ん"),
+    ("n'", "ん"),
+    ("xtu", "っ"),
+    ("ltu", "っ"),
+    ("xya", "ゃ"),
+    ("xyu", "ゅ"),
+    ("xyo", "ょ"),
+    ("-", "ー"),
+];
+
+fn romaji_table() -> HashMap<String, String> {
+    let mut table = HashMap::new();
+    for (consonant, kana) in KANA_ROWS.iter() {
+        for (vowel, kana) in ["a", "i", "u", "e", "o"].iter().zip(kana.iter()) {
+            table.insert(format!("{consonant}{vowel}"), kana.to_string());
+        }
+    }
+    for (consonant, kana) in KANA_CONTRACTED.iter() {
+        for (vowel, small) in [("a", "ゃ"), ("u", "ゅ"), ("e", "ぇ"), ("o", "ょ")] {
+            table.insert(format!("{consonant}{vowel}"), format!("{kana}{small}"));
+        }
+    }
+    for (romaji, kana) in KANA_SPECIAL.iter() {
+        table.insert(romaji.to_string(), kana.to_string());
+    }
+    table
+}
+
+/// Katakana spelling of hiragana
+fn katakana(hiragana: &str) -> String {
+    hiragana
+        .chars()
+        .map(|c| match c {
+            'ぁ'..='ゖ' => char::from_u32(c as u32 + 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// Japanese kana and kanji from romaji
+pub struct Kana {
+    dictionary: Dictionary,
+    table: HashMap<String, String>,
+    /// Converted hiragana, the reading looked up in the dictionary
+    kana: String,
+    /// Romaji not converted yet
+    romaji: String,
+}
+
+impl Kana {
+    pub fn new(dictionary: Dictionary) -> Self {
+        Self {
+            dictionary,
+            table: romaji_table(),
+            kana: String::new(),
+            romaji: String::new(),
+        }
+    }
+
+    /// Convert complete romaji at the start of the pending romaji
+    fn convert_romaji(&mut self) {
+        loop {
+            if let Some(kana) = self.table.get(&self.romaji) {
+                self.kana.push_str(kana);
+                self.romaji.clear();
+                return;
+            }
+            if self
+                .table
+                .keys()
+                .any(|romaji| romaji.starts_with(&self.romaji))
+            {
+                return;
+            }
+            let mut chars = self.romaji.chars();
+            let (Some(first), Some(second)) = (chars.next(), chars.next()) else {
+                return;
+            };
+            if first == 'n' && second != 'y' {
+                // N before another consonant
+                self.kana.push('ん');
+            } else if first == second && first != 'n' {
+                // Doubled consonants are written with a small tsu
+                self.kana.push('っ');
+            } else {
+                self.kana.push(first);
+            }
+            self.romaji.remove(0);
+        }
+    }
+
+    /// Reading including a final n
+    fn reading(&self) -> String {
+        if self.romaji == "n" {
+            format!("{}ん", self.kana)
+        } else {
+            format!("{}{}", self.kana, self.romaji)
+        }
+    }
+}
+
+impl Engine for Kana {
+    fn push(&mut self, c: char) -> bool {
+        if !(c.is_ascii_lowercase() || c == '-' || c == '\'' && self.romaji == "n") {
+            return false;
+        }
+        self.romaji.push(c);
+        self.convert_romaji();
+        true
+    }
+
+    fn pop(&mut self) -> bool {
+        self.romaji.pop().is_some() || self.kana.pop().is_some()
+    }
+
+    fn preedit(&self) -> String {
+        format!("{}{}", self.kana, self.romaji)
+    }
+
+    fn candidates(&self, limit: usize) -> Vec<Candidate> {
+        let reading = self.reading();
+        if reading.is_empty() {
+            return Vec::new();
+        }
+        let length = reading.chars().count();
+        let mut candidates = self.dictionary.convert(&reading, limit);
+        // The reading itself can always be typed as hiragana or katakana
+        for text in [reading.clone(), katakana(&reading)] {
+            if !candidates.iter().any(|candidate| candidate.text == text) {
+                candidates.push(Candidate { text, length });
+            }
+        }
+        candidates.truncate(limit.max(2));
+        candidates
+    }
+
+    fn consume(&mut self, candidate: &Candidate) {
+        if candidate.length >= self.kana.chars().count() {
+            self.reset();
+        } else {
+            remove_chars(&mut self.kana, candidate.length);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.kana.clear();
+        self.romaji.clear();
+    }
+}
+
+/// Compatibility jamo of initial consonants, in syllable order
+const CHOSEONG: &str = "ㄱㄲㄴㄷㄸㄹㅁㅂㅃㅅㅆㅇㅈㅉㅊㅋㅌㅍㅎ";
+
+/// Compatibility jamo of final consonants, in syllable order after no final
+const JONGSEONG: &str = "ㄱㄲㄳㄴㄵㄶㄷㄹㄺㄻㄼㄽㄾㄿㅀㅁㅂㅄㅅㅆㅇㅈㅊㅋㅌㅍㅎ";
+
+/// Vowels written with two keys
+const COMPOUND_VOWELS: &[(char, char, char)] = &[
+    ('ㅗ', 'ㅏ', 'ㅘ'),
+    ('ㅗ', 'ㅐ', 'ㅙ'),
+    ('ㅗ', 'ㅣ', 'ㅚ'),
+    ('ㅜ', 'ㅓ', 'ㅝ'),
+    ('ㅜ', 'ㅔ', 'ㅞ'),
+    ('ㅜ', 'ㅣ', 'ㅟ'),
+    ('ㅡ', 'ㅣ', 'ㅢ'),
+];
+
+/// Final consonant clusters written with two keys
+const COMPOUND_FINALS: &[(char, char, char)] = &[
+    ('ㄱ', 'ㅅ', 'ㄳ'),
+    ('ㄴ', 'ㅈ', 'ㄵ'),
+    ('ㄴ', 'ㅎ', 'ㄶ'),
+    ('ㄹ', 'ㄱ', 'ㄺ'),
+    ('ㄹ', 'ㅁ', 'ㄻ'),
+    ('ㄹ', 'ㅂ', 'ㄼ'),
+    ('ㄹ', 'ㅅ', 'ㄽ'),
+    ('ㄹ', 'ㅌ', 'ㄾ'),
+    ('ㄹ', 'ㅍ', 'ㄿ'),
+    ('ㄹ', 'ㅎ', 'ㅀ'),
+    ('ㅂ', 'ㅅ', 'ㅄ'),
+];
+
+/// Jamo typed by the letters of a QWERTY keyboard with the standard two set
+/// (dubeolsik) layout, as the Korean keymaps type Latin letters
+fn dubeolsik(c: char) -> Option<char> {
+    let jamo = match c {
+        'Q' => 'ㅃ',
+        'W' => 'ㅉ',
+        'E' => 'ㄸ',
+        'R' => 'ㄲ',
+        'T' => 'ㅆ',
+        'O' => 'ㅒ',
+        'P' => 'ㅖ',
+        _ => match c.to_ascii_lowercase() {
+            'q' => 'ㅂ',
+            'w' => 'ㅈ',
+            'e' => 'ㄷ',
+            'r' => 'ㄱ',
+            't' => 'ㅅ',
+            'y' => 'ㅛ',
+            'u' => 'ㅕ',
+            'i' => 'ㅑ',
+            'o' => 'ㅐ',
+            'p' => 'ㅔ',
+            'a' => 'ㅁ',
+            's' => 'ㄴ',
+            'd' => 'ㅇ',
+            'f' => 'ㄹ',
+            'g' => 'ㅎ',
+            'h' => 'ㅗ',
+            'j' => 'ㅓ',
+            'k' => 'ㅏ',
+            'l' => 'ㅣ',
+            'z' => 'ㅋ',
+            'x' => 'ㅌ',
+            'c' => 'ㅊ',
+            'v' => 'ㅍ',
+            'b' => 'ㅠ',
+            'n' => 'ㅜ',
+            'm' => 'ㅡ',
+            _ => return None,
+        },
+    };
+    Some(jamo)
+}
+
+fn is_vowel(c: char) -> bool {
+    ('ㅏ'..='ㅣ').contains(&c)
+}
+
+fn is_consonant(c: char) -> bool {
+    ('ㄱ'..='ㅎ').contains(&c)
+}
+
+/// Jamo written with two keys
+fn combine(table: &[(char, char, char)], first: char, second: char) -> Option<char> {
+    table
+        .iter()
+        .find(|(known_first, known_second, _)| *known_first == first && *known_second == second)
+        .map(|&(_, _, compound)| compound)
+}
+
+/// Syllable being composed from an initial consonant, a vowel and a final consonant
+#[derive(Clone, Copy, Default)]
+struct Syllable {
+    initial: Option<char>,
+    vowel: Option<char>,
+    last: Option<char>,
+}
+
+impl Syllable {
+    fn write(&self, text: &mut String) {
+        let position = |jamo: &str, c: char| jamo.chars().position(|known| known == c);
+        match (self.initial, self.vowel) {
+            (Some(initial), Some(vowel)) => {
+                let initial = position(CHOSEONG, initial).unwrap_or(0) as u32;
+                let vowel = vowel as u32 - 'ㅏ' as u32;
+                let last = self
+                    .last
+                    .and_then(|last| position(JONGSEONG, last))
+                    .map_or(0, |last| last as u32 + 1);
+                text.extend(char::from_u32(0xAC00 + (initial * 21 + vowel) * 28 + last));
+            }
+            _ => text.extend(self.initial.into_iter().chain(self.vowel)),
+        }
+    }
+
+    /// Write the syllable and start the next one
+    fn start(&mut self, text: &mut String, initial: Option<char>, vowel: Option<char>) {
+        self.write(text);
+        *self = Self {
+            initial,
+            vowel,
+            last: None,
+        };
+    }
+
+    /// Add a jamo, starting the next syllable if it does not fit
+    fn push(&mut self, c: char, text: &mut String) {
+        match (self.initial, self.vowel, self.last) {
+            (Some(_), None, _) if is_vowel(c) => self.vowel = Some(c),
+            (_, Some(vowel), None) if is_vowel(c) => match combine(COMPOUND_VOWELS, vowel, c) {
+                Some(compound) => self.vowel = Some(compound),
+                None => self.start(text, None, Some(c)),
+            },
+            (_, _, Some(last)) if is_vowel(c) => {
+                // The final consonant, or the second of a cluster, starts the next syllable
+                let split = COMPOUND_FINALS
+                    .iter()
+                    .find(|&&(_, _, compound)| compound == last);
+                let moved = match split {
+                    Some(&(first, second, _)) => {
+                        self.last = Some(first);
+                        second
+                    }
+                    None => {
+                        self.last = None;
+                        last
+                    }
+                };
+                self.start(text, Some(moved), Some(c));
+            }
+            _ if is_vowel(c) => self.start(text, None, Some(c)),
+            (Some(_), Some(_), None) if JONGSEONG.contains(c) => self.last = Some(c),
+            (Some(_), Some(_), Some(last)) => match combine(COMPOUND_FINALS, last, c) {
+                Some(compound) => self.last = Some(compound),
+                None => self.start(text, Some(c), None),
+            },
+            _ => self.start(text, Some(c), None),
+        }
+    }
+}
+
+/// Korean syllables from the jamo typed with a two set keyboard, or the
+/// letters of a QWERTY keyboard in the same positions
+pub struct Hangul {
+    dictionary: Dictionary,
+    jamo: Vec<char>,
+}
+
+impl Hangul {
+    pub fn new(dictionary: Dictionary) -> Self {
+        Self {
+            dictionary,
+            jamo: Vec::new(),
+        }
+    }
+
+    /// Compose all typed jamo into syllables
+    fn compose(&self) -> String {
+        let mut text = String::new();
+        let mut syllable = Syllable::default();
+        for &c in self.jamo.iter() {
+            syllable.push(c, &mut text);
+        }
+        syllable.write(&mut text);
+        text
+    }
+}
+
+impl Engine for Hangul {
+    fn push(&mut self, c: char) -> bool {
+        let c = match dubeolsik(c) {
+            Some(jamo) => jamo,
+            None if is_vowel(c) || is_consonant(c) => c,
+            None => return false,
+        };
+        self.jamo.push(c);
+        true
+    }
+
+    fn pop(&mut self) -> bool {
+        self.jamo.pop().is_some()
+    }
+
+    fn preedit(&self) -> String {
+        self.compose()
+    }
+
+    fn candidates(&self, limit: usize) -> Vec<Candidate> {
+        // Syllables are not split, so only the whole word is converted
+        let reading = self.compose();
+        let length = reading.chars().count();
+        self.dictionary
+            .convert(&reading, usize::MAX)
+            .into_iter()
+            .filter(|candidate| candidate.length == length)
+            .take(limit)
+            .collect()
+    }
+
+    fn consume(&mut self, _candidate: &Candidate) {
+        self.reset();
+    }
+
+    fn reset(&mut self) {
+        self.jamo.clear();
+    }
+
+    fn space_selects(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dictionary(data: &str) -> Dictionary {
+        let mut dictionary = Dictionary::default();
+        dictionary.parse(data);
+        dictionary
+    }
+
+    fn texts(candidates: &[Candidate]) -> Vec<(&str, usize)> {
+        candidates
+            .iter()
+            .map(|candidate| (candidate.text.as_str(), candidate.length))
+            .collect()
+    }
+
+    #[test]
+    fn dictionary_parse() {
+        let dictionary = dictionary(
+            "# comment\nni 你 700\nni 尼 20\nni 你 100\nbad line\nhao 好 x\nnihao 你好 500\n",
+        );
+        assert_eq!(
+            texts(&dictionary.convert("ni", 5))[..2],
+            [("你", 2), ("尼", 2)]
+        );
+        assert_eq!(
+            dictionary.entries["ni"],
+            [("你".to_string(), 800), ("尼".to_string(), 20)]
+        );
+        assert!(!dictionary.entries.contains_key("hao"));
+        assert!(!dictionary.entries.contains_key("bad"));
+    }
+
+    #[test]
+    fn dictionary_convert_order() {
+        let dictionary = dictionary("ni 你 700\nnihao 你好 500\nnimen 你们 300\nhao 好 500\n");
+        // Whole reading, then completions, then the longest prefix
+        assert_eq!(
+            texts(&dictionary.convert("ni", 5)),
+            [("你", 2), ("你好", 2), ("你们", 2)]
+        );
+        assert_eq!(
+            texts(&dictionary.convert("nihao", 5)),
+            [("你好", 5), ("你", 2)]
+        );
+        assert_eq!(
+            texts(&dictionary.convert("nih", 5)),
+            [("你好", 3), ("你", 2)]
+        );
+        assert_eq!(texts(&dictionary.convert("nihao", 1)), [("你好", 5)]);
+        assert!(dictionary.convert("xyz", 5).is_empty());
+    }
+
+    #[test]
+    fn starter_dictionaries_parse() {
+        for data in [
+            include_str!("../res/conversion/pinyin.txt"),
+            include_str!("../res/conversion/kana.txt"),
+            include_str!("../res/conversion/hangul.txt"),
+        ] {
+            for line in data.lines().filter(|line| !line.starts_with('#')) {
+                assert_eq!(dictionary(line).entries.len(), 1, "{line:?}");
+            }
+        }
+    }
+
+    fn push_all(engine: &mut dyn Engine, keys: &str) {
+        for c in keys.chars() {
+            assert!(engine.push(c), "{c:?}");
+        }
+    }
+
+    #[test]
+    fn pinyin_reading_and_consume() {
+        let mut pinyin = Pinyin::new(dictionary("ni 你 700\nhao 好 500\nnihao 你好 500\n"));
+        push_all(&mut pinyin, "ni'hao");
+        assert_eq!(pinyin.preedit(), "nihao");
+        assert_eq!(texts(&pinyin.candidates(1)), [("你好", 5)]);
+
+        let candidate = Candidate {
+            text: "你".to_string(),
+            length: 2,
+        };
+        pinyin.consume(&candidate);
+        assert_eq!(pinyin.preedit(), "hao");
+        assert_eq!(texts(&pinyin.candidates(1)), [("好", 3)]);
+
+        for c in ['A', '1', ' ', 'ü'] {
+            assert!(!pinyin.push(c), "{c:?}");
+        }
+        assert!(pinyin.pop());
+        assert_eq!(pinyin.preedit(), "ha");
+        pinyin.reset();
+        assert!(pinyin.is_empty());
+        // An apostrophe only separates syllables
+        assert!(!pinyin.push('\''));
+    }
+
+    fn kana(keys: &str) -> Kana {
+        let mut kana = Kana::new(Dictionary::default());
+        push_all(&mut kana, keys);
+        kana
+    }
+
+    #[test]
+    fn kana_romaji_table() {
+        assert_eq!(kana("a").preedit(), "あ");
+        assert_eq!(kana("ka").preedit(), "か");
+        assert_eq!(kana("shi").preedit(), "し");
+        assert_eq!(kana("si").preedit(), "し");
+        assert_eq!(kana("tsu").preedit(), "つ");
+        assert_eq!(kana("kyo").preedit(), "きょ");
+        assert_eq!(kana("sha").preedit(), "しゃ");
+        assert_eq!(kana("ja").preedit(), "じゃ");
+        assert_eq!(kana("fu").preedit(), "ふ");
+        assert_eq!(kana("ra-men").preedit(), "らーめn");
+        assert_eq!(kana("nihongo").preedit(), "にほんご");
+        // Incomplete romaji stays latin
+        assert_eq!(kana("k").preedit(), "k");
+        assert_eq!(kana("ky").preedit(), "ky");
+    }
+
+    #[test]
+    fn kana_small_tsu_and_n() {
+        assert_eq!(kana("kitte").preedit(), "きって");
+        assert_eq!(kana("zasshi").preedit(), "ざっし");
+        assert_eq!(kana("xtu").preedit(), "っ");
+        assert_eq!(kana("kanji").preedit(), "かんじ");
+        assert_eq!(kana("shinbun").preedit(), "しんぶn");
+        assert_eq!(kana("nn").preedit(), "ん");
+        assert_eq!(kana("kon'ya").preedit(), "こんや");
+        assert_eq!(kana("konya").preedit(), "こにゃ");
+        // A final n is converted as ん
+        assert_eq!(kana("hon").reading(), "ほん");
+        assert_eq!(kana("hon").preedit(), "ほn");
+        // An apostrophe only follows n
+        assert!(!kana("ka").push('\''));
+    }
+
+    #[test]
+    fn kana_candidates() {
+        let mut kana = Kana::new(dictionary("かんじ 漢字 800\nかん 缶 50\n"));
+        push_all(&mut kana, "kanji");
+        assert_eq!(
+            texts(&kana.candidates(5)),
+            [("漢字", 3), ("缶", 2), ("かんじ", 3), ("カンジ", 3)]
+        );
+        // The reading is offered even when the dictionary fills the limit
+        assert_eq!(texts(&kana.candidates(1)), [("漢字", 3), ("かんじ", 3)]);
+
+        kana.consume(&Candidate {
+            text: "缶".to_string(),
+            length: 2,
+        });
+        assert_eq!(kana.preedit(), "じ");
+        assert!(kana.pop());
+        assert!(kana.is_empty());
+        assert!(kana.candidates(5).is_empty());
+    }
+
+    fn hangul(keys: &str) -> Hangul {
+        let mut hangul = Hangul::new(Dictionary::default());
+        for c in keys.chars() {
+            assert!(hangul.push(c), "{c:?}");
+        }
+        hangul
+    }
+
+    fn compose(keys: &str) -> String {
+        hangul(keys).preedit()
+    }
+
+    #[test]
+    fn hangul_syllables() {
+        assert_eq!(compose("r"), "ㄱ");
+        assert_eq!(compose("k"), "ㅏ");
+        assert_eq!(compose("rk"), "가");
+        assert_eq!(compose("gks"), "한");
+        assert_eq!(compose("gksrmf"), "한글");
+        // Capitals type the tense consonants and the y vowels, or the same jamo
+        assert_eq!(compose("Rk"), "까");
+        assert_eq!(compose("Tk"), "싸");
+        assert_eq!(compose("dP"), "예");
+        assert_eq!(compose("GKS"), "한");
+        // Compatibility jamo of a Korean keymap
+        assert_eq!(compose("ㅎㅏㄴ"), "한");
+    }
+
+    #[test]
+    fn hangul_compound_vowels() {
+        assert_eq!(compose("rhk"), "과");
+        assert_eq!(compose("dnj"), "워");
+        assert_eq!(compose("dml"), "의");
+        assert_eq!(compose("dho"), "왜");
+        // Vowels that do not combine start a syllable without initial consonant
+        assert_eq!(compose("rkk"), "가ㅏ");
+    }
+
+    #[test]
+    fn hangul_finals() {
+        assert_eq!(compose("dlfr"), "읽");
+        assert_eq!(compose("rkqt"), "값");
+        assert_eq!(compose("dksg"), "않");
+        // Consonants that do not combine with the final start the next syllable
+        assert_eq!(compose("dlfrwl"), "읽지");
+        assert_eq!(compose("rkr"), "각");
+        assert_eq!(compose("rkrr"), "각ㄱ");
+        // Tense consonants are not finals
+        assert_eq!(compose("rkE"), "가ㄸ");
+    }
+
+    #[test]
+    fn hangul_final_moves_to_next_syllable() {
+        assert_eq!(compose("rkrk"), "가가");
+        assert_eq!(compose("tkfkd"), "사랑");
+        assert_eq!(compose("dkssud"), "안녕");
+        // The second consonant of a cluster moves
+        assert_eq!(compose("dlfrdj"), "읽어");
+        assert_eq!(compose("dlfrj"), "일거");
+        assert_eq!(compose("rkqtdl"), "값이");
+        assert_eq!(compose("rkqtl"), "갑시");
+    }
+
+    #[test]
+    fn hangul_pop_and_other_characters() {
+        let mut hangul = hangul("gksr");
+        assert_eq!(hangul.preedit(), "한ㄱ");
+        assert!(hangul.pop());
+        assert_eq!(hangul.preedit(), "한");
+        assert!(hangul.pop());
+        assert_eq!(hangul.preedit(), "하");
+        for c in ['1', ' ', '.', 'é', '가'] {
+            assert!(!hangul.push(c), "{c:?}");
+        }
+        hangul.reset();
+        assert!(hangul.is_empty());
+        assert!(!hangul.pop());
+    }
+}
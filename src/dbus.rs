@@ -0,0 +1,631 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use cosmic::iced::futures::{
+    SinkExt, StreamExt,
+    channel::mpsc::{self, Sender, UnboundedSender},
+};
+use std::collections::HashMap;
+use zbus::{
+    Connection, Proxy, connection, fdo, interface,
+    object_server::SignalContext,
+    zvariant::{ObjectPath, OwnedObjectPath, Value},
+};
+
+use crate::{Message, cli::Request, layout::LayoutKind};
+
+pub const NAME: &str = "com.system76.CosmicOSK";
+pub const PATH: &str = "/com/system76/CosmicOSK";
+
+/// Name of the keyboard presented by assistive technologies
+const APP_NAME: &str = "COSMIC On-Screen Keyboard";
+
+/// Interface used by GNOME and Phosh to show and hide Squeekboard
+pub const PURI_NAME: &str = "sm.puri.OSK0";
+pub const PURI_PATH: &str = "/sm/puri/OSK0";
+
+/// Launcher of the AT-SPI accessibility bus
+const A11Y_BUS_NAME: &str = "org.a11y.Bus";
+const A11Y_BUS_PATH: &str = "/org/a11y/bus";
+/// Registry making applications on the accessibility bus known to assistive
+/// technologies, which ignore events of unknown objects
+const REGISTRY_NAME: &str = "org.a11y.atspi.Registry";
+/// Root accessible of an application, announcements are emitted from it
+const ROOT_PATH: &str = "/org/a11y/atspi/accessible/root";
+const NULL_PATH: &str = "/org/a11y/atspi/null";
+/// `ATSPI_ROLE_APPLICATION`
+const ROLE_APPLICATION: u32 = 75;
+/// `ATSPI_LIVE_POLITE`, presented without interrupting current speech
+const LIVE_POLITE: i32 = 1;
+
+/// Screen reader service presenting messages as speech or braille, used when
+/// the keyboard is not registered on the accessibility bus
+const ORCA_NAME: &str = "org.gnome.Orca.Service";
+const ORCA_PATH: &str = "/org/gnome/Orca/Service";
+
+/// Virtual keyboard interface of fcitx5, owned while cooperating with it
+const FCITX_NAME: &str = "org.fcitx.Fcitx5.VirtualKeyboard";
+const FCITX_PATH: &str = "/org/fcitx/virtualkeyboard/impl";
+/// Interface of fcitx5 taking candidates chosen on the keyboard
+const FCITX_BACKEND_NAME: &str = "org.fcitx.Fcitx5";
+const FCITX_BACKEND_PATH: &str = "/virtualkeyboard";
+const FCITX_BACKEND_INTERFACE: &str = "org.fcitx.Fcitx5.VirtualKeyboardBackend1";
+
+/// State of the keyboard exposed as properties
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Status {
+    pub visible: bool,
+    pub layout: String,
+    pub height: u32,
+}
+
+/// Sent from the app to the D-Bus service
+#[derive(Clone, Debug)]
+pub enum Update {
+    Status(Status),
+    /// Message for assistive technologies such as screen readers
+    Announce(String),
+    /// Cooperate with an external input method instead of being one
+    ExternalIme(bool),
+    /// Choose a candidate of the external input method by index
+    SelectCandidate(usize),
+}
+
+/// Sent from an external input method to the app
+#[derive(Clone, Debug)]
+pub enum Ime {
+    Show,
+    Hide,
+    /// Candidates for the text being composed, none when composing ends
+    Candidates(Vec<String>),
+}
+
+struct Osk {
+    status: Status,
+    msg_tx: Sender<Message>,
+}
+
+impl Osk {
+    async fn send(&self, message: Message) -> fdo::Result<()> {
+        self.msg_tx
+            .clone()
+            .send(message)
+            .await
+            .map_err(|err| fdo::Error::Failed(err.to_string()))
+    }
+}
+
+#[interface(name = "com.system76.CosmicOSK")]
+impl Osk {
+    async fn show(&self) -> fdo::Result<()> {
+        self.send(Message::Request(Request::Show)).await
+    }
+
+    async fn hide(&self) -> fdo::Result<()> {
+        self.send(Message::Request(Request::Hide)).await
+    }
+
+    async fn toggle(&self) -> fdo::Result<()> {
+        self.send(Message::Request(Request::Toggle)).await
+    }
+
+    async fn set_layout(&self, name: &str) -> fdo::Result<()> {
+        let kind = LayoutKind::from_name(name)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("unknown layout {:?}", name)))?;
+        self.send(Message::Request(Request::Layout(kind))).await
+    }
+
+    async fn set_layer(&self, layer: u32) -> fdo::Result<()> {
+        self.send(Message::Layer(layer as usize)).await
+    }
+
+    #[zbus(property)]
+    async fn visible(&self) -> bool {
+        self.status.visible
+    }
+
+    #[zbus(property)]
+    async fn active_layout(&self) -> &str {
+        &self.status.layout
+    }
+
+    #[zbus(property)]
+    async fn height(&self) -> u32 {
+        self.status.height
+    }
+
+    #[zbus(signal)]
+    async fn visibility_changed(ctxt: &SignalContext<'_>, visible: bool) -> zbus::Result<()>;
+}
+
+struct Fcitx {
+    msg_tx: Sender<Message>,
+}
+
+impl Fcitx {
+    async fn send(&self, ime: Ime) -> fdo::Result<()> {
+        self.msg_tx
+            .clone()
+            .send(Message::Ime(ime))
+            .await
+            .map_err(|err| fdo::Error::Failed(err.to_string()))
+    }
+}
+
+/// Called by fcitx5 when it runs in virtual keyboard mode. The preedit text
+/// is shown by the focused application, so only candidates are used.
+#[interface(name = "org.fcitx.Fcitx5.VirtualKeyboard1")]
+impl Fcitx {
+    async fn show_virtual_keyboard(&self) -> fdo::Result<()> {
+        self.send(Ime::Show).await
+    }
+
+    async fn hide_virtual_keyboard(&self) -> fdo::Result<()> {
+        self.send(Ime::Hide).await
+    }
+
+    async fn update_candidate_area(
+        &self,
+        candidates: Vec<String>,
+        _has_prev: bool,
+        _has_next: bool,
+        _page_index: i32,
+        _cursor_index: i32,
+    ) -> fdo::Result<()> {
+        self.send(Ime::Candidates(candidates)).await
+    }
+
+    async fn update_preedit_area(&self, _preedit: &str) {}
+
+    async fn update_preedit_caret(&self, _caret: i32) {}
+
+    #[zbus(name = "NotifyIMActivated")]
+    async fn notify_im_activated(&self, _name: &str) {}
+
+    #[zbus(name = "NotifyIMDeactivated")]
+    async fn notify_im_deactivated(&self, _name: &str) {}
+
+    #[zbus(name = "NotifyIMListChanged")]
+    async fn notify_im_list_changed(&self) {}
+}
+
+struct Puri {
+    visible: bool,
+    msg_tx: Sender<Message>,
+}
+
+#[interface(name = "sm.puri.OSK0")]
+impl Puri {
+    async fn set_visible(&self, visible: bool) -> fdo::Result<()> {
+        let request = if visible {
+            Request::Show
+        } else {
+            Request::Hide
+        };
+        self.msg_tx
+            .clone()
+            .send(Message::Request(request))
+            .await
+            .map_err(|err| fdo::Error::Failed(err.to_string()))
+    }
+
+    #[zbus(property)]
+    async fn visible(&self) -> bool {
+        self.visible
+    }
+}
+
+/// Connect to the AT-SPI accessibility bus of the session
+async fn a11y_connect(conn: &Connection) -> zbus::Result<Connection> {
+    let reply = conn
+        .call_method(
+            Some(A11Y_BUS_NAME),
+            A11Y_BUS_PATH,
+            Some(A11Y_BUS_NAME),
+            "GetAddress",
+            &(),
+        )
+        .await?;
+    let address: String = reply.body().deserialize()?;
+    connection::Builder::address(address.as_str())?
+        .build()
+        .await
+}
+
+/// Reference to an accessible, its bus name and path
+type AccessibleRef = (String, OwnedObjectPath);
+
+fn accessible_ref(bus_name: &str, path: &'static str) -> AccessibleRef {
+    (
+        bus_name.to_string(),
+        ObjectPath::from_static_str_unchecked(path).into(),
+    )
+}
+
+/// Root accessible of the keyboard on the accessibility bus. Keys are
+/// exposed by the toolkit, this only gives announcements a known source.
+struct AtspiAccessible {
+    bus_name: String,
+    parent: AccessibleRef,
+}
+
+#[interface(name = "org.a11y.atspi.Accessible")]
+impl AtspiAccessible {
+    #[zbus(property)]
+    fn name(&self) -> &str {
+        APP_NAME
+    }
+
+    #[zbus(property)]
+    fn description(&self) -> &str {
+        ""
+    }
+
+    #[zbus(property)]
+    fn parent(&self) -> AccessibleRef {
+        self.parent.clone()
+    }
+
+    #[zbus(property)]
+    fn child_count(&self) -> i32 {
+        0
+    }
+
+    #[zbus(property)]
+    fn locale(&self) -> &str {
+        ""
+    }
+
+    #[zbus(property)]
+    fn accessible_id(&self) -> &str {
+        ""
+    }
+
+    fn get_child_at_index(&self, _index: i32) -> AccessibleRef {
+        accessible_ref("", NULL_PATH)
+    }
+
+    fn get_children(&self) -> Vec<AccessibleRef> {
+        Vec::new()
+    }
+
+    fn get_index_in_parent(&self) -> i32 {
+        -1
+    }
+
+    fn get_relation_set(&self) -> Vec<(u32, Vec<AccessibleRef>)> {
+        Vec::new()
+    }
+
+    fn get_role(&self) -> u32 {
+        ROLE_APPLICATION
+    }
+
+    fn get_role_name(&self) -> &str {
+        "application"
+    }
+
+    fn get_localized_role_name(&self) -> &str {
+        "application"
+    }
+
+    fn get_state(&self) -> Vec<u32> {
+        vec![0, 0]
+    }
+
+    fn get_attributes(&self) -> HashMap<String, String> {
+        HashMap::new()
+    }
+
+    fn get_application(&self) -> AccessibleRef {
+        accessible_ref(&self.bus_name, ROOT_PATH)
+    }
+
+    fn get_interfaces(&self) -> Vec<&str> {
+        vec!["org.a11y.atspi.Accessible", "org.a11y.atspi.Application"]
+    }
+}
+
+struct AtspiApplication {
+    /// Set by the registry
+    id: i32,
+}
+
+#[interface(name = "org.a11y.atspi.Application")]
+impl AtspiApplication {
+    #[zbus(property)]
+    fn toolkit_name(&self) -> &str {
+        "cosmic-osk"
+    }
+
+    #[zbus(property)]
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    #[zbus(property)]
+    fn atspi_version(&self) -> &str {
+        "2.1"
+    }
+
+    #[zbus(property)]
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    #[zbus(property)]
+    fn set_id(&mut self, id: i32) {
+        self.id = id;
+    }
+
+    fn get_locale(&self, _category: u32) -> String {
+        crate::localize::language()
+    }
+}
+
+/// Serve the root accessible and embed it in the registry, so assistive
+/// technologies present the events emitted from it
+async fn a11y_register(a11y: &Connection) -> zbus::Result<()> {
+    let bus_name = a11y
+        .unique_name()
+        .map(|name| name.to_string())
+        .unwrap_or_default();
+    let object_server = a11y.object_server();
+    object_server
+        .at(
+            ROOT_PATH,
+            AtspiAccessible {
+                bus_name: bus_name.clone(),
+                parent: accessible_ref("", NULL_PATH),
+            },
+        )
+        .await?;
+    object_server
+        .at(ROOT_PATH, AtspiApplication { id: 0 })
+        .await?;
+    let reply = a11y
+        .call_method(
+            Some(REGISTRY_NAME),
+            ROOT_PATH,
+            Some("org.a11y.atspi.Socket"),
+            "Embed",
+            &(accessible_ref(&bus_name, ROOT_PATH),),
+        )
+        .await?;
+    let parent: AccessibleRef = reply.body().deserialize()?;
+    let root = object_server
+        .interface::<_, AtspiAccessible>(ROOT_PATH)
+        .await?;
+    let mut root_mut = root.get_mut().await;
+    root_mut.parent = parent;
+    root_mut.parent_changed(root.signal_context()).await
+}
+
+/// Emit an AT-SPI announcement event from the registered root accessible,
+/// presented by any listening assistive technology
+async fn announce_atspi(a11y: &Connection, text: &str) -> zbus::Result<()> {
+    a11y.emit_signal(
+        None::<&str>,
+        ROOT_PATH,
+        "org.a11y.atspi.Event.Object",
+        "Announcement",
+        &(
+            "",
+            LIVE_POLITE,
+            0i32,
+            Value::from(text),
+            HashMap::<&str, Value>::new(),
+        ),
+    )
+    .await
+}
+
+/// Ask Orca to present a message, if the session has a screen reader enabled
+async fn present_message(
+    conn: &Connection,
+    a11y_status: &Proxy<'_>,
+    text: &str,
+) -> zbus::Result<()> {
+    if !a11y_status
+        .get_property::<bool>("ScreenReaderEnabled")
+        .await?
+    {
+        return Ok(());
+    }
+    conn.call_method(
+        Some(ORCA_NAME),
+        ORCA_PATH,
+        Some(ORCA_NAME),
+        "PresentMessage",
+        &(text,),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Own the fcitx5 virtual keyboard name while cooperating with an external
+/// input method, so fcitx5 sends its candidates to the keyboard
+async fn own_fcitx_name(conn: &Connection, external: bool) -> zbus::Result<()> {
+    if external {
+        conn.request_name(FCITX_NAME).await?;
+    } else {
+        conn.release_name(FCITX_NAME).await?;
+    }
+    Ok(())
+}
+
+/// Ask fcitx5 to commit one of its candidates
+async fn fcitx_select_candidate(conn: &Connection, index: usize) -> zbus::Result<()> {
+    conn.call_method(
+        Some(FCITX_BACKEND_NAME),
+        FCITX_BACKEND_PATH,
+        Some(FCITX_BACKEND_INTERFACE),
+        "SelectCandidate",
+        &(index as i32,),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Serve the session bus interfaces, updating properties from status sent
+/// over the channel passed to the app in [`Message::DbusTx`]
+pub async fn run(msg_tx: Sender<Message>) -> zbus::Result<()> {
+    serve(connection::Builder::session()?, msg_tx).await
+}
+
+/// Serve the interfaces on the bus of the connection builder, such as a
+/// private bus for testing
+pub async fn serve(
+    builder: connection::Builder<'_>,
+    mut msg_tx: Sender<Message>,
+) -> zbus::Result<()> {
+    let conn = builder
+        .name(NAME)?
+        .serve_at(
+            PATH,
+            Osk {
+                status: Status::default(),
+                msg_tx: msg_tx.clone(),
+            },
+        )?
+        .serve_at(
+            PURI_PATH,
+            Puri {
+                visible: false,
+                msg_tx: msg_tx.clone(),
+            },
+        )?
+        .serve_at(
+            FCITX_PATH,
+            Fcitx {
+                msg_tx: msg_tx.clone(),
+            },
+        )?
+        .build()
+        .await?;
+    // Another keyboard may own this name, which is not an error
+    if let Err(err) = conn.request_name(PURI_NAME).await {
+        log::info!("not providing {}: {}", PURI_NAME, err);
+    }
+
+    let (update_tx, mut update_rx) = mpsc::unbounded::<Update>();
+    if msg_tx.send(Message::DbusTx(update_tx)).await.is_err() {
+        return Ok(());
+    }
+
+    // Screen reader state of the AT-SPI accessibility bus
+    let a11y_status = Proxy::new(&conn, A11Y_BUS_NAME, A11Y_BUS_PATH, "org.a11y.Status").await?;
+    let a11y = match a11y_connect(&conn).await {
+        Ok(a11y) => match a11y_register(&a11y).await {
+            Ok(()) => Some(a11y),
+            Err(err) => {
+                log::info!(
+                    "failed to register with the accessibility registry: {}",
+                    err
+                );
+                None
+            }
+        },
+        Err(err) => {
+            log::info!("accessibility bus not available: {}", err);
+            None
+        }
+    };
+
+    let object_server = conn.object_server();
+    let osk = object_server.interface::<_, Osk>(PATH).await?;
+    let puri = object_server.interface::<_, Puri>(PURI_PATH).await?;
+    while let Some(update) = update_rx.next().await {
+        let status = match update {
+            Update::Status(status) => status,
+            Update::Announce(text) => {
+                let announced = match &a11y {
+                    Some(a11y) => match announce_atspi(a11y, &text).await {
+                        Ok(()) => true,
+                        Err(err) => {
+                            log::debug!("failed to emit announcement: {}", err);
+                            false
+                        }
+                    },
+                    None => false,
+                };
+                if !announced && let Err(err) = present_message(&conn, &a11y_status, &text).await {
+                    log::debug!("failed to announce: {}", err);
+                }
+                continue;
+            }
+            Update::ExternalIme(external) => {
+                // The external input method may not be fcitx5
+                if let Err(err) = own_fcitx_name(&conn, external).await {
+                    log::info!("not providing {}: {}", FCITX_NAME, err);
+                }
+                continue;
+            }
+            Update::SelectCandidate(index) => {
+                if let Err(err) = fcitx_select_candidate(&conn, index).await {
+                    log::warn!("failed to select candidate: {}", err);
+                }
+                continue;
+            }
+        };
+        let mut osk_mut = osk.get_mut().await;
+        if osk_mut.status == status {
+            continue;
+        }
+        let old = std::mem::replace(&mut osk_mut.status, status.clone());
+        let ctxt = osk.signal_context();
+        // A failed send only loses this change, so keep serving
+        if old.visible != status.visible {
+            if let Err(err) = osk_mut.visible_changed(ctxt).await {
+                log::warn!("failed to emit Visible change: {}", err);
+            }
+            if let Err(err) = Osk::visibility_changed(ctxt, status.visible).await {
+                log::warn!("failed to emit VisibilityChanged: {}", err);
+            }
+
+            let mut puri_mut = puri.get_mut().await;
+            puri_mut.visible = status.visible;
+            if let Err(err) = puri_mut.visible_changed(puri.signal_context()).await {
+                log::warn!("failed to emit {} Visible change: {}", PURI_NAME, err);
+            }
+        }
+        if old.layout != status.layout
+            && let Err(err) = osk_mut.active_layout_changed(ctxt).await
+        {
+            log::warn!("failed to emit ActiveLayout change: {}", err);
+        }
+        if old.height != status.height
+            && let Err(err) = osk_mut.height_changed(ctxt).await
+        {
+            log::warn!("failed to emit Height change: {}", err);
+        }
+    }
+    Ok(())
+}
+
+/// Send status to the D-Bus service, if it is running
+pub fn update(update_tx: &UnboundedSender<Update>, status: Status) {
+    if let Err(err) = update_tx.unbounded_send(Update::Status(status)) {
+        log::warn!("failed to update D-Bus status: {}", err);
+    }
+}
+
+/// Send a message for the screen reader to the D-Bus service
+pub fn announce(update_tx: &UnboundedSender<Update>, text: String) {
+    if let Err(err) = update_tx.unbounded_send(Update::Announce(text)) {
+        log::warn!("failed to send announcement: {}", err);
+    }
+}
+
+/// Tell the D-Bus service whether an external input method is used
+pub fn set_external_ime(update_tx: &UnboundedSender<Update>, external: bool) {
+    if let Err(err) = update_tx.unbounded_send(Update::ExternalIme(external)) {
+        log::warn!("failed to update external input method: {}", err);
+    }
+}
+
+/// Choose a candidate of the external input method
+pub fn select_candidate(update_tx: &UnboundedSender<Update>, index: usize) {
+    if let Err(err) = update_tx.unbounded_send(Update::SelectCandidate(index)) {
+        log::warn!("failed to select candidate: {}", err);
+    }
+}
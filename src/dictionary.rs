@@ -0,0 +1,302 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::predict::{self, Model};
+
+/// Learned and forgotten words
+#[derive(Debug, Default)]
+struct Words {
+    learned: Model,
+    forgotten: BTreeSet<String>,
+}
+
+impl Words {
+    fn read(path: &Path) -> io::Result<Self> {
+        let data = fs::read_to_string(path)?;
+        let mut words = Self::default();
+        let mut counts = String::new();
+        for line in data.lines() {
+            match line.strip_prefix('-') {
+                Some(word) => {
+                    words.forgotten.insert(word.trim().to_lowercase());
+                }
+                None => {
+                    counts.push_str(line);
+                    counts.push('\n');
+                }
+            }
+        }
+        words.learned.parse(&counts);
+        for word in words.forgotten.iter() {
+            words.learned.remove(word);
+        }
+        Ok(words)
+    }
+
+    /// Read a file, which is empty if it does not exist yet
+    fn read_or_default(path: &Path) -> io::Result<Self> {
+        match Self::read(path) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            result => result,
+        }
+    }
+
+    /// Add words learned and forgotten elsewhere, learning a word again if
+    /// it was forgotten
+    fn merge(&mut self, other: &Self) {
+        for word in other.forgotten.iter() {
+            self.learned.remove(word);
+            self.forgotten.insert(word.clone());
+        }
+        for (word, _) in other.learned.words() {
+            self.forgotten.remove(word);
+        }
+        self.learned.merge(&other.learned);
+    }
+
+    /// Write to a temporary file first, so the file is never left half written
+    fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_name);
+        let mut file = io::BufWriter::new(fs::File::create(&temp_path)?);
+        self.learned.write(&mut file)?;
+        for word in self.forgotten.iter() {
+            writeln!(file, "-{}", word)?;
+        }
+        file.into_inner()?.sync_all()?;
+        fs::rename(&temp_path, path)
+    }
+}
+
+/// Words learned from typing, stored as plain text in the user data directory.
+/// Lines use the n-gram file format, and lines starting with `-` name
+/// forgotten words that are never suggested.
+#[derive(Debug, Default)]
+pub struct UserDictionary {
+    path: Option<PathBuf>,
+    words: Words,
+    /// Words learned or forgotten since the last save, merged into the file
+    /// when saving as it may have been changed by an import
+    changes: Words,
+}
+
+impl UserDictionary {
+    pub fn default_path() -> Option<PathBuf> {
+        predict::data_dirs()
+            .into_iter()
+            .next()
+            .map(|data_home| data_home.join("cosmic-osk/dictionary.txt"))
+    }
+
+    pub fn load() -> Self {
+        let mut dictionary = Self::default();
+        dictionary.path = Self::default_path();
+        if let Some(path) = &dictionary.path {
+            match Words::read_or_default(path) {
+                Ok(words) => dictionary.words = words,
+                Err(err) => log::warn!("failed to load {}: {}", path.display(), err),
+            }
+        }
+        dictionary
+    }
+
+    /// Merge words from a plain text file, saved with the other changes.
+    /// Words already forgotten stay forgotten.
+    pub fn import(&mut self, path: &Path) -> io::Result<()> {
+        let mut words = Words::read(path)?;
+        for word in self.words.forgotten.iter() {
+            words.learned.remove(word);
+        }
+        self.words.merge(&words);
+        self.changes.merge(&words);
+        Ok(())
+    }
+
+    pub fn export(&self, path: &Path) -> io::Result<()> {
+        self.words.write(path)
+    }
+
+    /// Merge changes into the saved file
+    pub fn save(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = Words::read_or_default(path).and_then(|mut words| {
+            words.merge(&self.changes);
+            words.write(path)?;
+            Ok(words)
+        });
+        match result {
+            Ok(words) => {
+                self.words = words;
+                self.changes = Words::default();
+            }
+            Err(err) => log::warn!("failed to save {}: {}", path.display(), err),
+        }
+    }
+
+    /// Save if words were learned or forgotten since the last save
+    pub fn save_changes(&mut self) {
+        if self.has_changes() {
+            self.save();
+        }
+    }
+
+    pub fn has_changes(&self) -> bool {
+        !self.changes.learned.is_empty() || !self.changes.forgotten.is_empty()
+    }
+
+    /// Add learned words to a prediction model and remove forgotten ones
+    pub fn apply(&self, model: &mut Model) {
+        model.merge(&self.words.learned);
+        for word in self.words.forgotten.iter() {
+            model.remove(word);
+        }
+    }
+
+    /// Record an accepted word, updating the model used for predictions
+    pub fn learn(&mut self, model: &mut Model, previous: Option<&str>, word: &str) {
+        let word = word.to_lowercase();
+        if word.is_empty() || !word.chars().any(char::is_alphabetic) {
+            return;
+        }
+        let mut learned = Model::default();
+        learned.add_unigram(&word, 1);
+        if let Some(previous) = previous {
+            learned.add_bigram(previous, &word, 1);
+        }
+        model.merge(&learned);
+        let learned = Words {
+            learned,
+            forgotten: BTreeSet::new(),
+        };
+        self.words.merge(&learned);
+        self.changes.merge(&learned);
+    }
+
+    pub fn forget(&mut self, model: &mut Model, word: &str) {
+        model.remove(word);
+        let forgotten = Words {
+            learned: Model::default(),
+            forgotten: BTreeSet::from([word.to_lowercase()]),
+        };
+        self.words.merge(&forgotten);
+        self.changes.merge(&forgotten);
+    }
+}
+
+impl Drop for UserDictionary {
+    fn drop(&mut self) {
+        self.save_changes();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dictionary(path: &Path) -> UserDictionary {
+        let mut dictionary = UserDictionary::default();
+        dictionary.path = Some(path.to_path_buf());
+        dictionary
+    }
+
+    fn words(words: &Words) -> Vec<(String, u64)> {
+        let mut words: Vec<_> = words
+            .learned
+            .words()
+            .map(|(word, count)| (word.to_string(), count))
+            .collect();
+        words.sort();
+        words
+    }
+
+    #[test]
+    fn import_export_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut model = Model::default();
+        let mut original = dictionary(&dir.path().join("dictionary.txt"));
+        original.learn(&mut model, None, "Hello");
+        original.learn(&mut model, Some("hello"), "world");
+        original.forget(&mut model, "teh");
+        let exported = dir.path().join("export/words.txt");
+        original.export(&exported).unwrap();
+
+        let mut imported = dictionary(&dir.path().join("other.txt"));
+        imported.import(&exported).unwrap();
+        assert_eq!(words(&imported.words), words(&original.words));
+        assert_eq!(imported.words.forgotten, original.words.forgotten);
+        assert!(imported.has_changes());
+    }
+
+    #[test]
+    fn forget_and_learn() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut model = Model::default();
+        let mut dictionary = dictionary(&dir.path().join("dictionary.txt"));
+        dictionary.learn(&mut model, None, "cosmic");
+        assert_eq!(model.suggest(None, "cos", 1), ["cosmic"]);
+
+        dictionary.forget(&mut model, "Cosmic");
+        assert!(model.suggest(None, "cos", 1).is_empty());
+        assert!(words(&dictionary.words).is_empty());
+        assert!(dictionary.words.forgotten.contains("cosmic"));
+
+        // Typing a forgotten word again learns it again
+        dictionary.learn(&mut model, None, "cosmic");
+        assert_eq!(words(&dictionary.words), [("cosmic".to_string(), 1)]);
+        assert!(dictionary.words.forgotten.is_empty());
+
+        // Numbers and punctuation are not learned
+        dictionary.learn(&mut model, None, "1234");
+        assert_eq!(words(&dictionary.words).len(), 1);
+    }
+
+    #[test]
+    fn import_keeps_forgotten_words() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("words.txt");
+        fs::write(&file, "teh\t5\nthe\t3\n").unwrap();
+        let mut model = Model::default();
+        let mut dictionary = dictionary(&dir.path().join("dictionary.txt"));
+        dictionary.forget(&mut model, "teh");
+        dictionary.import(&file).unwrap();
+        assert_eq!(words(&dictionary.words), [("the".to_string(), 3)]);
+        assert!(dictionary.words.forgotten.contains("teh"));
+    }
+
+    #[test]
+    fn save_merges_changes_into_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dictionary.txt");
+        let mut model = Model::default();
+        let mut running = dictionary(&path);
+        running.learn(&mut model, None, "typed");
+
+        // Another process imports words while this one is running
+        let file = dir.path().join("words.txt");
+        fs::write(&file, "imported\t2\n").unwrap();
+        let mut other = dictionary(&path);
+        other.import(&file).unwrap();
+        other.save();
+        assert!(!other.has_changes());
+
+        running.save();
+        assert!(!running.has_changes());
+        assert_eq!(
+            words(&Words::read(&path).unwrap()),
+            [("imported".to_string(), 2), ("typed".to_string(), 1)]
+        );
+        assert!(!dir.path().join("dictionary.txt.tmp").exists());
+    }
+}
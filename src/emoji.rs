@@ -0,0 +1,256 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use rust_embed::RustEmbed;
+use std::sync::OnceLock;
+
+#[derive(RustEmbed)]
+#[folder = "res/emoji/"]
+struct EmojiTables;
+
+static EMOJIS: OnceLock<Vec<Emoji>> = OnceLock::new();
+
+/// Fitzpatrick skin tone modifiers, from light to dark
+pub const SKIN_TONES: [char; 5] = [
+    '\u{1F3FB}',
+    '\u{1F3FC}',
+    '\u{1F3FD}',
+    '\u{1F3FE}',
+    '\u{1F3FF}',
+];
+
+/// Number of recently used emoji to remember
+pub const RECENT_MAX: usize = 32;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Category {
+    Recent,
+    Smileys,
+    People,
+    Nature,
+    Food,
+    Travel,
+    Activities,
+    Objects,
+    Symbols,
+}
+
+impl Category {
+    pub const ALL: &'static [Self] = &[
+        Self::Recent,
+        Self::Smileys,
+        Self::People,
+        Self::Nature,
+        Self::Food,
+        Self::Travel,
+        Self::Activities,
+        Self::Objects,
+        Self::Symbols,
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "smileys" => Some(Self::Smileys),
+            "people" => Some(Self::People),
+            "nature" => Some(Self::Nature),
+            "food" => Some(Self::Food),
+            "travel" => Some(Self::Travel),
+            "activities" => Some(Self::Activities),
+            "objects" => Some(Self::Objects),
+            "symbols" => Some(Self::Symbols),
+            _ => None,
+        }
+    }
+
+    /// Emoji shown on the category tab
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Self::Recent => "🕘",
+            Self::Smileys => "😀",
+            Self::People => "👋",
+            Self::Nature => "🐻",
+            Self::Food => "🍔",
+            Self::Travel => "🚗",
+            Self::Activities => "⚽",
+            Self::Objects => "💡",
+            Self::Symbols => "🔣",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Emoji {
+    pub emoji: String,
+    pub category: Category,
+    /// Supports skin tone modifiers
+    pub tones: bool,
+    /// Name and keywords, lowercase
+    pub annotations: Vec<String>,
+}
+
+impl Emoji {
+    /// Apply a skin tone modifier, dropping any emoji presentation selector
+    pub fn with_tone(&self, tone: char) -> String {
+        let mut chars = self.emoji.chars();
+        let mut emoji = String::new();
+        emoji.extend(chars.next());
+        emoji.push(tone);
+        emoji.extend(chars.filter(|&c| c != '\u{FE0F}'));
+        emoji
+    }
+}
+
+fn parse(table: &str) -> Vec<Emoji> {
+    let mut emojis = Vec::new();
+    for line in table.lines() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split('\t');
+        let (Some(category), Some(emoji), Some(tones), Some(annotations)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            log::warn!("invalid emoji table line {:?}", line);
+            continue;
+        };
+        let Some(category) = Category::from_name(category) else {
+            log::warn!("unknown emoji category {:?}", category);
+            continue;
+        };
+        emojis.push(Emoji {
+            emoji: emoji.to_string(),
+            category,
+            tones: tones == "1",
+            annotations: annotations
+                .split('|')
+                .map(|annotation| annotation.trim().to_lowercase())
+                .filter(|annotation| !annotation.is_empty())
+                .collect(),
+        });
+    }
+    emojis
+}
+
+/// All emoji, annotated in the current language if a table exists for it
+pub fn emojis() -> &'static [Emoji] {
+    EMOJIS.get_or_init(|| {
+        let language = crate::localize::language();
+        let file = EmojiTables::get(&format!("{}.tsv", language))
+            .or_else(|| EmojiTables::get("en.tsv"))
+            .expect("English emoji table is embedded");
+        parse(&String::from_utf8_lossy(&file.data))
+    })
+}
+
+/// Strip skin tone modifiers and presentation selectors
+fn base(emoji: &str) -> impl Iterator<Item = char> + '_ {
+    emoji
+        .chars()
+        .filter(|c| *c != '\u{FE0F}' && !SKIN_TONES.contains(c))
+}
+
+/// Find an emoji, ignoring any skin tone modifier
+pub fn find(emoji: &str) -> Option<&'static Emoji> {
+    emojis().iter().find(|e| base(&e.emoji).eq(base(emoji)))
+}
+
+pub fn category(category: Category) -> impl Iterator<Item = &'static Emoji> {
+    emojis().iter().filter(move |e| e.category == category)
+}
+
+/// Emoji with annotations matching all words of the query, best matches first
+pub fn search(query: &str) -> Vec<&'static Emoji> {
+    search_in(emojis(), query)
+}
+
+fn search_in<'a>(emojis: &'a [Emoji], query: &str) -> Vec<&'a Emoji> {
+    let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    if words.is_empty() {
+        return Vec::new();
+    }
+    let mut results: Vec<(usize, &'a Emoji)> = emojis
+        .iter()
+        .filter_map(|e| {
+            let mut score = 0;
+            for word in words.iter() {
+                if e.annotations.iter().any(|a| a == word) {
+                    score += 2;
+                } else if e.annotations.iter().any(|a| a.contains(word.as_str())) {
+                    score += 1;
+                } else {
+                    return None;
+                }
+            }
+            Some((score, e))
+        })
+        .collect();
+    // Stable sort keeps table order for equal scores
+    results.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+    results.into_iter().map(|(_, e)| e).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(name: &str) -> Vec<Emoji> {
+        let file = EmojiTables::get(name).unwrap();
+        parse(&String::from_utf8_lossy(&file.data))
+    }
+
+    fn found(results: &[&Emoji]) -> Vec<String> {
+        results.iter().map(|e| e.emoji.clone()).collect()
+    }
+
+    #[test]
+    fn parse_tables() {
+        for name in EmojiTables::iter() {
+            assert!(!table(&name).is_empty(), "{name} has no emoji");
+        }
+    }
+
+    #[test]
+    fn search_empty_query() {
+        let emojis = table("en.tsv");
+        assert!(search_in(&emojis, "").is_empty());
+        assert!(search_in(&emojis, "   ").is_empty());
+    }
+
+    #[test]
+    fn search_matches_all_words() {
+        let emojis = table("en.tsv");
+        let results = search_in(&emojis, "cat face");
+        assert!(found(&results).contains(&"🐱".to_string()));
+        for e in results {
+            assert!(e.annotations.iter().any(|a| a.contains("cat")));
+            assert!(e.annotations.iter().any(|a| a.contains("face")));
+        }
+        assert!(search_in(&emojis, "cat zzzz").is_empty());
+    }
+
+    #[test]
+    fn search_ignores_case_and_finds_partial_words() {
+        let emojis = table("en.tsv");
+        assert_eq!(
+            found(&search_in(&emojis, "GRINNING face")),
+            found(&search_in(&emojis, "grinning face"))
+        );
+        assert!(found(&search_in(&emojis, "grin")).contains(&"😀".to_string()));
+    }
+
+    #[test]
+    fn search_ranks_exact_words_first() {
+        let emojis = table("en.tsv");
+        let results = search_in(&emojis, "cat");
+        let first = results.first().unwrap();
+        assert!(first.annotations.iter().any(|a| a == "cat"));
+        let partial = results
+            .iter()
+            .position(|e| !e.annotations.iter().any(|a| a == "cat"));
+        let exact = results
+            .iter()
+            .rposition(|e| e.annotations.iter().any(|a| a == "cat"));
+        if let (Some(partial), Some(exact)) = (partial, exact) {
+            assert!(exact < partial);
+        }
+    }
+}
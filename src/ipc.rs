@@ -0,0 +1,75 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    env, fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    time::Duration,
+};
+
+use crate::cli::Request;
+
+/// Socket of the running keyboard, in the user's runtime directory
+pub fn socket_path() -> Option<PathBuf> {
+    let runtime_dir = env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty())?;
+    let display = env::var("WAYLAND_DISPLAY").unwrap_or_else(|_| "wayland-0".to_string());
+    Some(PathBuf::from(runtime_dir).join(format!("cosmic-osk-{}.sock", display)))
+}
+
+/// Send requests to the running keyboard, returning false if there is none
+pub fn send(requests: &[Request]) -> io::Result<bool> {
+    let Some(path) = socket_path() else {
+        return Ok(false);
+    };
+    let mut stream = match UnixStream::connect(&path) {
+        Ok(stream) => stream,
+        Err(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+            ) =>
+        {
+            return Ok(false);
+        }
+        Err(err) => return Err(err),
+    };
+    for request in requests.iter() {
+        writeln!(stream, "{}", request)?;
+    }
+    Ok(true)
+}
+
+/// Listen for requests, replacing the socket of a keyboard that is gone
+pub fn listen() -> io::Result<UnixListener> {
+    let path = socket_path()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "XDG_RUNTIME_DIR is not set"))?;
+    match UnixListener::bind(&path) {
+        Err(err) if err.kind() == io::ErrorKind::AddrInUse => {
+            if UnixStream::connect(&path).is_ok() {
+                return Err(err);
+            }
+            fs::remove_file(&path)?;
+            UnixListener::bind(&path)
+        }
+        result => result,
+    }
+}
+
+/// Read requests from a connection until it is closed
+pub fn read_requests(stream: UnixStream) -> impl Iterator<Item = Request> {
+    // Do not let a stuck client block other requests
+    if let Err(err) = stream.set_read_timeout(Some(Duration::from_secs(1))) {
+        log::warn!("failed to set request timeout: {}", err);
+    }
+    BufReader::new(stream)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| match line.parse() {
+            Ok(request) => Some(request),
+            Err(err) => {
+                log::warn!("{}", err);
+                None
+            }
+        })
+}
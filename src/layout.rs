@@ -1,28 +1,960 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
+use icu_properties::{GeneralCategoryGroup, maps};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use xkbcommon::xkb;
 
-#[derive(Clone, Copy, Debug)]
+use crate::{fl, scan::ScanMethod};
+
+pub const LAYER_NORMAL: usize = 0;
+pub const LAYER_SHIFT: usize = 1;
+pub const LAYER_SYMBOLS: usize = 2;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Action {
     None,
     Keycode(xkb::Keycode),
+    /// Press a keycode while holding the shift keycode
+    Shifted {
+        keycode: xkb::Keycode,
+        shift: xkb::Keycode,
+    },
+    Layer(LayerSwitch),
+    /// Show or hide the numeric keypad beside the keyboard
+    Numpad,
+    /// Open the emoji picker
+    Emoji,
+    /// Open the settings window
+    Settings,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LayerSwitch {
+    /// Show the layer while the key is held
+    Momentary(usize),
+    /// Show the layer until the key is pressed again
+    Toggle(usize),
+    /// Show the layer for the next key press, pressing twice locks it
+    OneShot(usize),
+}
+
+impl LayerSwitch {
+    /// Layer switched to
+    pub fn layer(&self) -> usize {
+        match *self {
+            Self::Momentary(layer) | Self::Toggle(layer) | Self::OneShot(layer) => layer,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum LayoutKind {
+    #[default]
+    Full,
+    Compact,
+    Numpad,
+}
+
+impl LayoutKind {
+    pub const ALL: &'static [Self] = &[Self::Full, Self::Compact, Self::Numpad];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::Compact => "compact",
+            Self::Numpad => "numpad",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.name() == name)
+    }
+
+    pub fn layout(&self, keymap: &xkb::Keymap) -> Layout {
+        match self {
+            Self::Full => Layout::from(keymap),
+            Self::Compact => Layout::compact(keymap),
+            Self::Numpad => Layout::numpad(keymap),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct Key {
     pub name: String,
+    /// Symbolic icon shown instead of the name, if the icon theme has it
+    pub icon: Option<&'static str>,
     pub width: f32,
     pub action: Action,
 }
 
+/// Position of a key in a layer, as row and column
+pub type KeyPos = (usize, usize);
+
 #[derive(Clone, Debug, Default)]
 pub struct Layer {
     pub rows: Vec<Vec<Key>>,
+    /// Groups of keys in the order used by group scanning
+    pub scan_groups: Vec<Vec<KeyPos>>,
+}
+
+impl Layer {
+    /// Split rows into groups of keys of even width, about the given width in key units
+    fn split_scan_groups(&mut self, width: f32) {
+        self.scan_groups.clear();
+        for (row, keys) in self.rows.iter().enumerate() {
+            let row_width: f32 = keys.iter().map(|key| key.width).sum();
+            let count = (row_width / width).round().max(1.0);
+            let group_width = row_width / count;
+            let start = self.scan_groups.len();
+            let mut x = 0.0;
+            for (column, key) in keys.iter().enumerate() {
+                // Keys belong to the group containing their center
+                let group =
+                    (((x + key.width / 2.0) / group_width) as usize).min(count as usize - 1);
+                if start + group >= self.scan_groups.len() {
+                    self.scan_groups.push(Vec::new());
+                }
+                self.scan_groups.last_mut().unwrap().push((row, column));
+                x += key.width;
+            }
+        }
+    }
+
+    /// Reverse the order of keys in every row, scanning groups from right to left
+    fn mirror(&mut self) {
+        for keys in self.rows.iter_mut() {
+            keys.reverse();
+        }
+        for group in self.scan_groups.iter_mut() {
+            for (row, column) in group.iter_mut() {
+                *column = self.rows[*row].len() - 1 - *column;
+            }
+        }
+    }
+
+    /// Key at a position in key units from the top left of the layer
+    pub fn key_at(&self, x: f32, y: f32) -> Option<KeyPos> {
+        if x < 0.0 || y < 0.0 {
+            return None;
+        }
+        let row = y as usize;
+        let mut right = 0.0;
+        for (column, key) in self.rows.get(row)?.iter().enumerate() {
+            right += key.width;
+            if x < right {
+                return Some((row, column));
+            }
+        }
+        None
+    }
+
+    /// Groups of keys highlighted together by switch scanning, skipping keys without an action
+    pub fn scan_order(&self, method: ScanMethod) -> Vec<Vec<KeyPos>> {
+        let groups: Vec<Vec<KeyPos>> = match method {
+            ScanMethod::Group if !self.scan_groups.is_empty() => self.scan_groups.clone(),
+            _ => self
+                .rows
+                .iter()
+                .enumerate()
+                .map(|(row, keys)| (0..keys.len()).map(|column| (row, column)).collect())
+                .collect(),
+        };
+        groups
+            .into_iter()
+            .map(|group| {
+                group
+                    .into_iter()
+                    .filter(|&(row, column)| {
+                        self.rows
+                            .get(row)
+                            .and_then(|keys| keys.get(column))
+                            .is_some_and(|key| key.action != Action::None)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .filter(|group| !group.is_empty())
+            .collect()
+    }
+}
+
+/// Kind of key, used to style keys differently
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyClass {
+    /// Types a character
+    Character,
+    /// Changes what other keys type, such as Shift or a layer switch
+    Modifier,
+    /// Any other key, such as Backspace or an arrow
+    Function,
+    Enter,
+}
+
+/// Classes of keycodes that do not type a character on any level
+fn key_classes(keymap: &xkb::Keymap) -> HashMap<xkb::Keycode, KeyClass> {
+    let mut classes = HashMap::new();
+    if keymap.num_layouts() == 0 {
+        return classes;
+    }
+    for kc_raw in keymap.min_keycode().raw()..=keymap.max_keycode().raw() {
+        let kc = xkb::Keycode::new(kc_raw);
+        let Some(&sym) = keymap.key_get_syms_by_level(kc, 0, 0).first() else {
+            continue;
+        };
+        let printable = (0..keymap.num_levels_for_key(kc, 0))
+            .any(|level| level_char(keymap, kc, level).is_some());
+        let class = if sym.is_modifier_key() {
+            KeyClass::Modifier
+        } else if sym == xkb::Keysym::Return || sym == xkb::Keysym::KP_Enter {
+            KeyClass::Enter
+        } else if !printable {
+            KeyClass::Function
+        } else {
+            continue;
+        };
+        classes.insert(kc, class);
+    }
+    classes
+}
+
+/// Keysyms of every key at the given shift level
+fn key_keysyms(keymap: &xkb::Keymap, level: u32) -> HashMap<xkb::Keycode, xkb::Keysym> {
+    let mut keysyms = HashMap::new();
+    if keymap.num_layouts() == 0 {
+        return keysyms;
+    }
+    for kc_raw in keymap.min_keycode().raw()..=keymap.max_keycode().raw() {
+        let kc = xkb::Keycode::new(kc_raw);
+        if let Some(&sym) = keymap.key_get_syms_by_level(kc, 0, level).first() {
+            keysyms.insert(kc, sym);
+        }
+    }
+    keysyms
+}
+
+/// Keycodes used by drag gestures on the space and backspace keys
+#[derive(Clone, Copy, Debug)]
+pub struct GestureKeys {
+    pub space: xkb::Keycode,
+    pub backspace: xkb::Keycode,
+    pub left: xkb::Keycode,
+    pub right: xkb::Keycode,
+    pub shift: xkb::Keycode,
+    pub ctrl: xkb::Keycode,
+}
+
+impl GestureKeys {
+    fn new(keymap: &xkb::Keymap) -> Option<Self> {
+        Some(Self {
+            space: keymap.key_by_name("SPCE")?,
+            backspace: keymap.key_by_name("BKSP")?,
+            left: keymap.key_by_name("LEFT")?,
+            right: keymap.key_by_name("RGHT")?,
+            shift: keymap.key_by_name("LFSH")?,
+            ctrl: keymap.key_by_name("LCTL")?,
+        })
+    }
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct Layout {
+    pub kind: LayoutKind,
     pub layers: Vec<Layer>,
+    pub gesture_keys: Option<GestureKeys>,
+    /// Classes of keycodes that do not type a character
+    pub classes: HashMap<xkb::Keycode, KeyClass>,
+    /// Keysym of every keycode without modifiers, used to name keys
+    pub keysyms: HashMap<xkb::Keycode, xkb::Keysym>,
+    /// Keysym of every keycode with shift, typed by shifted keys
+    pub shift_keysyms: HashMap<xkb::Keycode, xkb::Keysym>,
+    /// Letters are written right to left, so rows and keys pointing along the text are mirrored
+    pub rtl: bool,
+}
+
+/// Localized label of a keysym that does not type a visible character
+fn keysym_label(sym: xkb::Keysym) -> Option<String> {
+    use xkb::Keysym;
+    let label = match sym {
+        Keysym::BackSpace => fl!("label-backspace"),
+        Keysym::Delete | Keysym::KP_Delete => fl!("label-delete"),
+        Keysym::Insert | Keysym::KP_Insert => fl!("label-insert"),
+        Keysym::Home | Keysym::KP_Home => fl!("label-home"),
+        Keysym::End | Keysym::KP_End => fl!("label-end"),
+        Keysym::Prior | Keysym::KP_Prior => fl!("label-page-up"),
+        Keysym::Next | Keysym::KP_Next => fl!("label-page-down"),
+        Keysym::Left | Keysym::KP_Left => fl!("label-left"),
+        Keysym::Right | Keysym::KP_Right => fl!("label-right"),
+        Keysym::Up | Keysym::KP_Up => fl!("label-up"),
+        Keysym::Down | Keysym::KP_Down => fl!("label-down"),
+        Keysym::KP_Begin => fl!("label-begin"),
+        Keysym::Return | Keysym::KP_Enter => fl!("label-enter"),
+        Keysym::Tab | Keysym::ISO_Left_Tab => fl!("label-tab"),
+        Keysym::Escape => fl!("label-escape"),
+        Keysym::Caps_Lock => fl!("label-caps-lock"),
+        Keysym::Num_Lock => fl!("label-num-lock"),
+        Keysym::Shift_L | Keysym::Shift_R => fl!("label-shift"),
+        Keysym::Control_L | Keysym::Control_R => fl!("label-ctrl"),
+        Keysym::Alt_L | Keysym::Alt_R => fl!("label-alt"),
+        Keysym::ISO_Level3_Shift => fl!("label-alt-gr"),
+        Keysym::Super_L | Keysym::Super_R => fl!("label-super"),
+        Keysym::Multi_key => fl!("label-compose"),
+        _ => return None,
+    };
+    Some(label)
+}
+
+/// Symbol shown instead of the label of a keysym when key icons are enabled
+fn keysym_symbol(sym: xkb::Keysym) -> Option<&'static str> {
+    use xkb::Keysym;
+    let symbol = match sym {
+        Keysym::BackSpace => "⌫",
+        Keysym::Delete | Keysym::KP_Delete => "⌦",
+        Keysym::Return | Keysym::KP_Enter => "⏎",
+        Keysym::Tab => "⇥",
+        Keysym::ISO_Left_Tab => "⇤",
+        Keysym::Shift_L | Keysym::Shift_R => "⇧",
+        Keysym::Caps_Lock => "⇪",
+        Keysym::Escape => "⎋",
+        Keysym::Left | Keysym::KP_Left => "←",
+        Keysym::Right | Keysym::KP_Right => "→",
+        Keysym::Up | Keysym::KP_Up => "↑",
+        Keysym::Down | Keysym::KP_Down => "↓",
+        Keysym::Home | Keysym::KP_Home => "⇱",
+        Keysym::End | Keysym::KP_End => "⇲",
+        Keysym::Prior | Keysym::KP_Prior => "⇞",
+        Keysym::Next | Keysym::KP_Next => "⇟",
+        _ => return None,
+    };
+    Some(symbol)
+}
+
+/// Icon of the key opening the emoji picker
+const EMOJI_ICON: &str = "face-smile-symbolic";
+
+/// Icon of the key opening the settings window
+const SETTINGS_ICON: &str = "preferences-system-symbolic";
+
+/// Icon of shift keys, also used by the compact layout's shift layer key
+const SHIFT_ICON: &str = "keyboard-shift-filled-symbolic";
+
+/// Symbolic icon of a keysym that does not type a visible character
+fn keysym_icon(sym: xkb::Keysym) -> Option<&'static str> {
+    use xkb::Keysym;
+    let icon = match sym {
+        Keysym::BackSpace => "edit-clear-symbolic",
+        Keysym::Return | Keysym::KP_Enter => "keyboard-enter-symbolic",
+        Keysym::Tab => "format-indent-more-symbolic",
+        Keysym::ISO_Left_Tab => "format-indent-less-symbolic",
+        Keysym::Shift_L | Keysym::Shift_R => SHIFT_ICON,
+        Keysym::Caps_Lock => "keyboard-caps-lock-filled-symbolic",
+        Keysym::Super_L | Keysym::Super_R => "start-here-symbolic",
+        Keysym::Left | Keysym::KP_Left => "go-previous-symbolic",
+        Keysym::Right | Keysym::KP_Right => "go-next-symbolic",
+        Keysym::Up | Keysym::KP_Up => "go-up-symbolic",
+        Keysym::Down | Keysym::KP_Down => "go-down-symbolic",
+        Keysym::Print => "camera-photo-symbolic",
+        Keysym::Menu => "open-menu-symbolic",
+        Keysym::Undo => "edit-undo-symbolic",
+        Keysym::Redo => "edit-redo-symbolic",
+        Keysym::Find => "edit-find-symbolic",
+        Keysym::Help => "help-browser-symbolic",
+        Keysym::XF86_AudioMute => "audio-volume-muted-symbolic",
+        Keysym::XF86_AudioLowerVolume => "audio-volume-low-symbolic",
+        Keysym::XF86_AudioRaiseVolume => "audio-volume-high-symbolic",
+        Keysym::XF86_AudioMicMute => "microphone-sensitivity-muted-symbolic",
+        Keysym::XF86_AudioPlay => "media-playback-start-symbolic",
+        Keysym::XF86_AudioPause => "media-playback-pause-symbolic",
+        Keysym::XF86_AudioStop => "media-playback-stop-symbolic",
+        Keysym::XF86_AudioPrev => "media-skip-backward-symbolic",
+        Keysym::XF86_AudioNext => "media-skip-forward-symbolic",
+        Keysym::XF86_AudioRewind => "media-seek-backward-symbolic",
+        Keysym::XF86_AudioForward => "media-seek-forward-symbolic",
+        Keysym::XF86_AudioRecord => "media-record-symbolic",
+        Keysym::XF86_Eject => "media-eject-symbolic",
+        Keysym::XF86_MonBrightnessUp | Keysym::XF86_MonBrightnessDown => {
+            "display-brightness-symbolic"
+        }
+        Keysym::XF86_KbdBrightnessUp
+        | Keysym::XF86_KbdBrightnessDown
+        | Keysym::XF86_KbdLightOnOff => "keyboard-brightness-symbolic",
+        Keysym::XF86_Display => "video-display-symbolic",
+        Keysym::XF86_TouchpadToggle => "input-touchpad-symbolic",
+        Keysym::XF86_WLAN => "network-wireless-symbolic",
+        Keysym::XF86_Bluetooth => "bluetooth-active-symbolic",
+        Keysym::XF86_PowerOff => "system-shutdown-symbolic",
+        Keysym::XF86_ScreenSaver => "system-lock-screen-symbolic",
+        Keysym::XF86_Search => "system-search-symbolic",
+        Keysym::XF86_Calculator => "accessories-calculator-symbolic",
+        Keysym::XF86_Explorer | Keysym::XF86_MyComputer => "system-file-manager-symbolic",
+        Keysym::XF86_WWW | Keysym::XF86_HomePage => "web-browser-symbolic",
+        Keysym::XF86_Mail => "mail-unread-symbolic",
+        Keysym::XF86_Tools => "preferences-system-symbolic",
+        Keysym::XF86_Favorites => "starred-symbolic",
+        Keysym::XF86_Back => "go-previous-symbolic",
+        Keysym::XF86_Forward => "go-next-symbolic",
+        Keysym::XF86_Refresh | Keysym::XF86_Reload => "view-refresh-symbolic",
+        Keysym::XF86_Stop => "process-stop-symbolic",
+        Keysym::XF86_Copy => "edit-copy-symbolic",
+        Keysym::XF86_Cut => "edit-cut-symbolic",
+        Keysym::XF86_Paste => "edit-paste-symbolic",
+        Keysym::XF86_Close => "window-close-symbolic",
+        Keysym::XF86_ZoomIn => "zoom-in-symbolic",
+        Keysym::XF86_ZoomOut => "zoom-out-symbolic",
+        _ => return None,
+    };
+    Some(icon)
+}
+
+/// Short labels of vendor keysyms, by keysym name without the `XF86` prefix
+const XF86_LABELS: &[(&str, &str)] = &[
+    ("AudioMute", "Mute"),
+    ("AudioLowerVolume", "Vol−"),
+    ("AudioRaiseVolume", "Vol+"),
+    ("AudioMicMute", "Mic"),
+    ("AudioPlay", "Play"),
+    ("AudioPause", "Pause"),
+    ("AudioStop", "Stop"),
+    ("AudioPrev", "Prev"),
+    ("AudioNext", "Next"),
+    ("AudioRewind", "Rew"),
+    ("AudioForward", "Fwd"),
+    ("AudioRecord", "Rec"),
+    ("AudioRepeat", "Repeat"),
+    ("AudioRandomPlay", "Shuffle"),
+    ("AudioCycleTrack", "Track"),
+    ("AudioMedia", "Media"),
+    ("AudioPreset", "Preset"),
+    ("MonBrightnessUp", "Bright+"),
+    ("MonBrightnessDown", "Bright−"),
+    ("MonBrightnessCycle", "Bright"),
+    ("KbdBrightnessUp", "Light+"),
+    ("KbdBrightnessDown", "Light−"),
+    ("KbdLightOnOff", "Light"),
+    ("PowerOff", "Power"),
+    ("PowerDown", "Power"),
+    ("WakeUp", "Wake"),
+    ("ScreenSaver", "Lock"),
+    ("Calculator", "Calc"),
+    ("Calculater", "Calc"),
+    ("Explorer", "Files"),
+    ("MyComputer", "Files"),
+    ("Documents", "Docs"),
+    ("Pictures", "Photos"),
+    ("Terminal", "Term"),
+    ("Calendar", "Cal"),
+    ("Messenger", "Chat"),
+    ("HomePage", "Web"),
+    ("WWW", "Web"),
+    ("Favorites", "Fav"),
+    ("Refresh", "Reload"),
+    ("Forward", "Fwd"),
+    ("TouchpadToggle", "Touchpad"),
+    ("TouchpadOn", "Touchpad"),
+    ("TouchpadOff", "Touchpad"),
+    ("WLAN", "Wi-Fi"),
+    ("WWAN", "Mobile"),
+    ("Bluetooth", "BT"),
+    ("RFKill", "Airplane"),
+    ("Display", "Display"),
+    ("SplitScreen", "Split"),
+    ("RotateWindows", "Rotate"),
+    ("RotationLockToggle", "Rotation"),
+    ("ZoomIn", "Zoom+"),
+    ("ZoomOut", "Zoom−"),
+    ("LogOff", "Log Off"),
+    ("ContrastAdjust", "Contrast"),
+    ("Battery", "Batt"),
+    ("Keyboard", "Kbd"),
+];
+
+/// Words dropped from the front of vendor keysym names that are not in `XF86_LABELS`
+const XF86_PREFIXES: &[&str] = &["Audio", "Mon", "Kbd", "Switch"];
+
+/// Readable label of a keysym name, shortening vendor keysyms such as `XF86AudioMute`
+fn keysym_short_name(sym: xkb::Keysym) -> String {
+    let name = xkb::keysym_get_name(sym);
+    let Some(vendor) = name.strip_prefix("XF86") else {
+        return name;
+    };
+    if let Some((_, label)) = XF86_LABELS.iter().find(|(key, _)| *key == vendor) {
+        return label.to_string();
+    }
+    // Split at underscores and capitals, keeping acronyms and numbers together
+    let chars: Vec<char> = vendor.chars().collect();
+    let mut words: Vec<String> = Vec::new();
+    for (i, &c) in chars.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1);
+        let boundary = prev.is_some_and(|prev| {
+            if c.is_ascii_digit() {
+                prev.is_alphabetic()
+            } else {
+                c.is_uppercase()
+                    && (prev.is_lowercase()
+                        || ((prev.is_uppercase() || prev.is_ascii_digit())
+                            && next.is_some_and(|next| next.is_lowercase())))
+            }
+        });
+        if c == '_' {
+            words.push(String::new());
+            continue;
+        }
+        match words.last_mut() {
+            Some(word) if !boundary => word.push(c),
+            _ => words.push(c.to_string()),
+        }
+    }
+    words.retain(|word| !word.is_empty());
+    if words.len() > 1 && XF86_PREFIXES.contains(&words[0].as_str()) {
+        words.remove(0);
+    }
+    words.join(" ")
+}
+
+/// Returns true for characters of scripts written right to left
+fn is_rtl_char(c: char) -> bool {
+    // Hebrew, Arabic, Syriac, Thaana, N'Ko and their extensions and presentation forms
+    matches!(c, '\u{0590}'..='\u{08FF}' | '\u{FB1D}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}')
+}
+
+/// Returns true if most letters of the keymap are in a right to left script
+fn is_rtl(keymap: &xkb::Keymap) -> bool {
+    let letter_keys = ["AD", "AC", "AB"]
+        .iter()
+        .flat_map(|row| (1..=10).map(move |column| format!("{row}{column:02}")));
+    let (mut rtl, mut letters) = (0, 0);
+    for name in letter_keys {
+        let Some(c) = keymap
+            .key_by_name(&name)
+            .and_then(|kc| level_char(keymap, kc, 0))
+            .filter(|c| c.is_alphabetic())
+        else {
+            continue;
+        };
+        letters += 1;
+        if is_rtl_char(c) {
+            rtl += 1;
+        }
+    }
+    rtl * 2 > letters
+}
+
+/// Returns true for marks that combine with the previous character, such as Arabic harakat
+fn is_combining(c: char) -> bool {
+    GeneralCategoryGroup::Mark.contains(maps::general_category().get(c))
+}
+
+/// Label of a typed character, showing combining marks on a dotted circle and
+/// naming invisible joiners and direction marks
+fn char_label(c: char) -> String {
+    match c {
+        '\u{200C}' => "ZWNJ".to_string(),
+        '\u{200D}' => "ZWJ".to_string(),
+        '\u{200E}' => "LRM".to_string(),
+        '\u{200F}' => "RLM".to_string(),
+        c if is_combining(c) => format!("\u{25CC}{c}"),
+        c => c.to_string(),
+    }
+}
+
+/// Spacing or combining form of the diacritic added by a dead key
+fn dead_key_char(sym: xkb::Keysym) -> Option<char> {
+    use xkb::Keysym;
+    let c = match sym {
+        Keysym::dead_grave => '`',
+        Keysym::dead_acute => '´',
+        Keysym::dead_circumflex => '^',
+        Keysym::dead_tilde => '~',
+        Keysym::dead_macron => '¯',
+        Keysym::dead_breve => '˘',
+        Keysym::dead_abovedot => '˙',
+        Keysym::dead_diaeresis => '¨',
+        Keysym::dead_abovering => '˚',
+        Keysym::dead_doubleacute => '˝',
+        Keysym::dead_caron => 'ˇ',
+        Keysym::dead_cedilla => '¸',
+        Keysym::dead_ogonek => '˛',
+        Keysym::dead_iota => 'ͺ',
+        Keysym::dead_voiced_sound => '゛',
+        Keysym::dead_semivoiced_sound => '゜',
+        Keysym::dead_stroke => '/',
+        Keysym::dead_currency => '¤',
+        Keysym::dead_lowline => '_',
+        Keysym::dead_greek => 'µ',
+        Keysym::dead_belowdot => '\u{0323}',
+        Keysym::dead_hook => '\u{0309}',
+        Keysym::dead_horn => '\u{031B}',
+        Keysym::dead_abovecomma => '\u{0313}',
+        Keysym::dead_abovereversedcomma => '\u{0314}',
+        Keysym::dead_doublegrave => '\u{030F}',
+        Keysym::dead_belowring => '\u{0325}',
+        Keysym::dead_belowmacron => '\u{0331}',
+        Keysym::dead_belowcircumflex => '\u{032D}',
+        Keysym::dead_belowtilde => '\u{0330}',
+        Keysym::dead_belowbreve => '\u{032E}',
+        Keysym::dead_belowdiaeresis => '\u{0324}',
+        Keysym::dead_invertedbreve => '\u{0311}',
+        Keysym::dead_belowcomma => '\u{0326}',
+        Keysym::dead_aboveverticalline => '\u{030D}',
+        Keysym::dead_belowverticalline => '\u{0329}',
+        Keysym::dead_longsolidusoverlay => '\u{0338}',
+        _ => return None,
+    };
+    Some(c)
+}
+
+/// Label of a key typing the keysym, as shown on the keyboard
+pub fn key_label(sym: xkb::Keysym) -> String {
+    match sym.key_char().or_else(|| dead_key_char(sym)) {
+        Some(c) if !c.is_control() => char_label(c),
+        _ => keysym_label(sym).unwrap_or_else(|| keysym_short_name(sym)),
+    }
+}
+
+/// Get a printable label for the keysym at the given shift level of a key
+fn level_label(keymap: &xkb::Keymap, kc: xkb::Keycode, level: u32) -> Option<String> {
+    let sym = *keymap.key_get_syms_by_level(kc, 0, level).first()?;
+    Some(key_label(sym))
+}
+
+/// Get the icon for the keysym at the given shift level of a key
+fn level_icon(keymap: &xkb::Keymap, kc: xkb::Keycode, level: u32) -> Option<&'static str> {
+    keysym_icon(*keymap.key_get_syms_by_level(kc, 0, level).first()?)
+}
+
+/// Get the character produced at the given shift level of a key, if printable
+fn level_char(keymap: &xkb::Keymap, kc: xkb::Keycode, level: u32) -> Option<char> {
+    let sym = *keymap.key_get_syms_by_level(kc, 0, level).first()?;
+    sym.key_char().filter(|c| !c.is_control())
+}
+
+impl Layout {
+    /// Total width of the widest row in any layer, in key units
+    pub fn width(&self) -> f32 {
+        self.layers
+            .iter()
+            .flat_map(|layer| layer.rows.iter())
+            .map(|row| row.iter().map(|key| key.width).sum::<f32>())
+            .fold(0.0, f32::max)
+    }
+
+    pub fn key_class(&self, key: &Key) -> KeyClass {
+        self.action_class(key.action)
+    }
+
+    /// Class of a key by its action
+    pub fn action_class(&self, action: Action) -> KeyClass {
+        match action {
+            Action::Keycode(kc) | Action::Shifted { keycode: kc, .. } => self
+                .classes
+                .get(&kc)
+                .copied()
+                .unwrap_or(KeyClass::Character),
+            Action::Layer(_) => KeyClass::Modifier,
+            Action::None | Action::Numpad | Action::Emoji | Action::Settings => KeyClass::Function,
+        }
+    }
+
+    /// Symbol replacing the label of a special key when key icons are enabled
+    pub fn key_symbol(&self, key: &Key) -> Option<&'static str> {
+        let symbol = match key.action {
+            Action::Keycode(kc) => self.keysyms.get(&kc).and_then(|&sym| keysym_symbol(sym)),
+            Action::Layer(LayerSwitch::OneShot(LAYER_SHIFT)) => Some("⇧"),
+            _ => None,
+        };
+        // Backspace and tab point along the text
+        match symbol {
+            Some("⌫") if self.rtl => Some("⌦"),
+            Some("⇥") if self.rtl => Some("⇤"),
+            Some("⇤") if self.rtl => Some("⇥"),
+            _ => symbol,
+        }
+    }
+
+    /// Icon of a key, mirrored for right to left layouts
+    pub fn key_icon(&self, key: &Key) -> Option<&'static str> {
+        match key.icon {
+            Some("edit-clear-symbolic") if self.rtl => Some("edit-clear-rtl-symbolic"),
+            Some("format-indent-more-symbolic") if self.rtl => Some("format-indent-less-symbolic"),
+            Some("format-indent-less-symbolic") if self.rtl => Some("format-indent-more-symbolic"),
+            icon => icon,
+        }
+    }
+
+    /// Keysym typed by a key, if it types one
+    pub fn action_keysym(&self, action: Action) -> Option<xkb::Keysym> {
+        match action {
+            Action::Keycode(kc) => self.keysyms.get(&kc).copied(),
+            Action::Shifted { keycode, .. } => self.shift_keysyms.get(&keycode).copied(),
+            _ => None,
+        }
+    }
+
+    /// Mirror the order of keys of right to left layouts
+    fn mirror_rtl(mut self) -> Self {
+        if self.rtl {
+            for layer in self.layers.iter_mut() {
+                layer.mirror();
+            }
+        }
+        self
+    }
+
+    /// Number of rows of the tallest layer
+    pub fn rows(&self) -> usize {
+        self.layers
+            .iter()
+            .map(|layer| layer.rows.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Phone style layout with letters, shift, and symbols layers
+    pub fn compact(keymap: &xkb::Keymap) -> Self {
+        if keymap.num_layouts() == 0 {
+            return Layout::default();
+        }
+
+        let shift = keymap.key_by_name("LFSH");
+        let key = |name: &str, width: f32| match keymap.key_by_name(name) {
+            Some(kc) => Key {
+                name: level_label(keymap, kc, 0).unwrap_or_else(|| name.to_string()),
+                icon: level_icon(keymap, kc, 0),
+                width,
+                action: Action::Keycode(kc),
+            },
+            None => {
+                log::warn!("cannot find keycode for {:?} in keymap", name);
+                Key {
+                    name: name.to_string(),
+                    icon: None,
+                    width,
+                    action: Action::None,
+                }
+            }
+        };
+        let layer_key = |label: String, width: f32, switch: LayerSwitch| Key {
+            name: label,
+            icon: (switch == LayerSwitch::OneShot(LAYER_SHIFT)).then_some(SHIFT_ICON),
+            width,
+            action: Action::Layer(switch),
+        };
+        let letter = |name: &str, level: u32| -> Key {
+            let Some(kc) = keymap.key_by_name(name) else {
+                log::warn!("cannot find keycode for {:?} in keymap", name);
+                return Key {
+                    name: name.to_string(),
+                    icon: None,
+                    width: 1.0,
+                    action: Action::None,
+                };
+            };
+            let action = match (level, shift) {
+                (0, _) => Action::Keycode(kc),
+                (_, Some(shift)) => Action::Shifted { keycode: kc, shift },
+                (_, None) => Action::Keycode(kc),
+            };
+            Key {
+                name: level_label(keymap, kc, level).unwrap_or_else(|| name.to_string()),
+                icon: None,
+                width: 1.0,
+                action,
+            }
+        };
+
+        let letter_rows: &[&[&str]] = &[
+            &[
+                "AD01", "AD02", "AD03", "AD04", "AD05", "AD06", "AD07", "AD08", "AD09", "AD10",
+            ],
+            &[
+                "AC01", "AC02", "AC03", "AC04", "AC05", "AC06", "AC07", "AC08", "AC09",
+            ],
+            &["AB01", "AB02", "AB03", "AB04", "AB05", "AB06", "AB07"],
+        ];
+
+        let mut layers = Vec::with_capacity(3);
+        for level in [0, 1] {
+            let mut layer = Layer::default();
+            for (i, letter_row) in letter_rows.iter().enumerate() {
+                let mut row: Vec<Key> = letter_row.iter().map(|name| letter(name, level)).collect();
+                if i == letter_rows.len() - 1 {
+                    row.insert(
+                        0,
+                        layer_key(fl!("label-shift"), 1.5, LayerSwitch::OneShot(LAYER_SHIFT)),
+                    );
+                    row.push(key("BKSP", 1.5));
+                }
+                layer.rows.push(row);
+            }
+            layer.rows.push(vec![
+                layer_key(
+                    fl!("label-symbols"),
+                    1.5,
+                    LayerSwitch::Toggle(LAYER_SYMBOLS),
+                ),
+                Key {
+                    name: "☺".to_string(),
+                    icon: Some(EMOJI_ICON),
+                    width: 1.0,
+                    action: Action::Emoji,
+                },
+                letter("AB08", 0),
+                key("SPCE", 4.0),
+                letter("AB09", 0),
+                key("RTRN", 1.5),
+            ]);
+            layer.split_scan_groups(5.0);
+            layers.push(layer);
+        }
+
+        // Collect digits and punctuation reachable on the first two levels
+        let symbol_keys: &[&str] = &[
+            "AE01", "AE02", "AE03", "AE04", "AE05", "AE06", "AE07", "AE08", "AE09", "AE10", "TLDE",
+            "AE11", "AE12", "AD11", "AD12", "BKSL", "AC10", "AC11", "AB08", "AB09", "AB10",
+        ];
+        let mut digits = Vec::new();
+        let mut symbols = Vec::new();
+        for &name in symbol_keys.iter() {
+            let Some(kc) = keymap.key_by_name(name) else {
+                continue;
+            };
+            for level in [0, 1] {
+                let Some(c) = level_char(keymap, kc, level) else {
+                    continue;
+                };
+                if c.is_alphabetic() || c.is_whitespace() {
+                    continue;
+                }
+                if digits
+                    .iter()
+                    .chain(symbols.iter())
+                    .any(|k: &Key| k.name == char_label(c))
+                {
+                    continue;
+                }
+                let action = match (level, shift) {
+                    (0, _) => Action::Keycode(kc),
+                    (_, Some(shift)) => Action::Shifted { keycode: kc, shift },
+                    (_, None) => continue,
+                };
+                let symbol_key = Key {
+                    name: char_label(c),
+                    icon: None,
+                    width: 1.0,
+                    action,
+                };
+                if c.is_numeric() {
+                    digits.push(symbol_key);
+                } else {
+                    symbols.push(symbol_key);
+                }
+            }
+        }
+
+        let mut symbols_layer = Layer::default();
+        if !digits.is_empty() {
+            symbols_layer.rows.push(digits);
+        }
+        let columns = symbols.len().div_ceil(2).max(1);
+        for chunk in symbols.chunks(columns) {
+            symbols_layer.rows.push(chunk.to_vec());
+        }
+        symbols_layer.rows.push(vec![
+            layer_key(fl!("label-letters"), 1.5, LayerSwitch::Toggle(LAYER_NORMAL)),
+            Key {
+                name: fl!("label-numpad"),
+                icon: None,
+                width: 1.0,
+                action: Action::Numpad,
+            },
+            Key {
+                name: "⚙".to_string(),
+                icon: Some(SETTINGS_ICON),
+                width: 1.0,
+                action: Action::Settings,
+            },
+            key("SPCE", 3.0),
+            key("BKSP", 1.5),
+            key("RTRN", 1.5),
+        ]);
+        symbols_layer.split_scan_groups(5.0);
+        layers.push(symbols_layer);
+
+        Layout {
+            kind: LayoutKind::Compact,
+            layers,
+            gesture_keys: GestureKeys::new(keymap),
+            classes: key_classes(keymap),
+            keysyms: key_keysyms(keymap, 0),
+            shift_keysyms: key_keysyms(keymap, 1),
+            rtl: is_rtl(keymap),
+        }
+        .mirror_rtl()
+    }
+
+    /// Numeric keypad with one layer per Num Lock state, indexed by whether it is active
+    pub fn numpad(keymap: &xkb::Keymap) -> Self {
+        if keymap.num_layouts() == 0 {
+            return Layout::default();
+        }
+
+        let key_rows: &[&[&str]] = &[
+            &["NMLK", "KPDV", "KPMU", "KPSU"],
+            &["KP7", "KP8", "KP9", "KPAD"],
+            &["KP4", "KP5", "KP6", "BKSP"],
+            &["KP1", "KP2", "KP3", "KPEN"],
+            &["KP0", "KPDL", "TAB"],
+        ];
+
+        let mut layers = Vec::with_capacity(2);
+        for level in [0, 1] {
+            let mut layer = Layer::default();
+            for key_row in key_rows.iter() {
+                let mut row = Vec::with_capacity(key_row.len());
+                for &name in key_row.iter() {
+                    let mut key = Key {
+                        name: name.to_string(),
+                        icon: None,
+                        width: if name == "KP0" { 2.0 } else { 1.0 },
+                        action: Action::None,
+                    };
+                    match keymap.key_by_name(name) {
+                        Some(kc) => {
+                            key.action = Action::Keycode(kc);
+                            // Keys without a Num Lock level use the same label on both layers
+                            let key_level = level.min(keymap.num_levels_for_key(kc, 0).max(1) - 1);
+                            key.icon = level_icon(keymap, kc, key_level);
+                            if let Some(label) = level_label(keymap, kc, key_level) {
+                                key.name = label
+                                    .strip_prefix("KP_")
+                                    .map(str::to_string)
+                                    .unwrap_or(label);
+                            }
+                        }
+                        None => {
+                            log::warn!("cannot find keycode for {:?} in keymap", name);
+                        }
+                    }
+                    row.push(key);
+                }
+                layer.rows.push(row);
+            }
+            // Rows are small enough to be groups
+            layer.split_scan_groups(4.0);
+            layers.push(layer);
+        }
+
+        Layout {
+            kind: LayoutKind::Numpad,
+            layers,
+            gesture_keys: None,
+            classes: key_classes(keymap),
+            keysyms: key_keysyms(keymap, 0),
+            shift_keysyms: key_keysyms(keymap, 1),
+            rtl: is_rtl(keymap),
+        }
+    }
 }
 
 impl From<&xkb::Keymap> for Layout {
@@ -53,7 +985,9 @@ impl From<&xkb::Keymap> for Layout {
                 "AB10", "RTSH", "UP", "INS",
             ],
             &[
-                "LCTL", "LALT", "LWIN", "SPCE", "RALT", "RWIN", "RCTL", "LEFT", "DOWN", "RGHT",
+                // The right Super and Ctrl keys make room for the emoji, numpad, and settings keys
+                "LCTL", "LALT", "LWIN", "SPCE", "RALT", "EMOJI", "NUM", "SETTINGS", "LEFT", "DOWN",
+                "RGHT",
             ],
         ];
 
@@ -65,11 +999,13 @@ impl From<&xkb::Keymap> for Layout {
             for &key in key_row.iter() {
                 let mut normal_key = Key {
                     name: key.to_string(),
+                    icon: None,
                     width: 1.0,
                     action: Action::None,
                 };
                 let mut shift_key = Key {
                     name: key.to_string(),
+                    icon: None,
                     width: 1.0,
                     action: Action::None,
                 };
@@ -81,63 +1017,79 @@ impl From<&xkb::Keymap> for Layout {
 
                         let normal_syms = keymap.key_get_syms_by_level(kc, 0, 0);
                         if let Some(normal_sym) = normal_syms.get(0) {
-                            normal_key.name = xkb::keysym_get_name(*normal_sym);
-                            if let Some(normal_char) = normal_sym.key_char() {
-                                if !normal_char.is_control() {
-                                    normal_key.name = normal_char.to_string();
-                                }
-                            }
+                            normal_key.name = key_label(*normal_sym);
+                            normal_key.icon = keysym_icon(*normal_sym);
 
                             // Copy normal key name over by default
                             shift_key.name = normal_key.name.clone();
+                            shift_key.icon = normal_key.icon;
                         }
 
+                        // Shifted keysyms that are not characters or dead keys keep the normal label
                         let shift_syms = keymap.key_get_syms_by_level(kc, 0, 1);
                         if let Some(shift_sym) = shift_syms.get(0) {
-                            shift_key.name = xkb::keysym_get_name(*shift_sym);
-                            if let Some(shift_char) = shift_sym.key_char() {
+                            if let Some(shift_char) =
+                                shift_sym.key_char().or_else(|| dead_key_char(*shift_sym))
+                            {
                                 if !shift_char.is_control() {
-                                    shift_key.name = shift_char.to_string();
+                                    shift_key.name = char_label(shift_char);
+                                    shift_key.icon = None;
+                                    // Type the shifted character without holding shift
+                                    if let Some(shift) = keymap.key_by_name("LFSH") {
+                                        shift_key.action = Action::Shifted { keycode: kc, shift };
+                                    }
                                 }
                             }
                         }
                     }
+                    None if key == "EMOJI" => {
+                        // Not a real key, opens the emoji picker
+                        normal_key.action = Action::Emoji;
+                        shift_key.action = Action::Emoji;
+                        normal_key.name = "☺".to_string();
+                        shift_key.name = "☺".to_string();
+                        normal_key.icon = Some(EMOJI_ICON);
+                        shift_key.icon = Some(EMOJI_ICON);
+                    }
+                    None if key == "NUM" => {
+                        // Not a real key, toggles the numeric keypad
+                        normal_key.action = Action::Numpad;
+                        shift_key.action = Action::Numpad;
+                        normal_key.name = fl!("label-numpad");
+                        shift_key.name = fl!("label-numpad");
+                    }
+                    None if key == "SETTINGS" => {
+                        // Not a real key, opens the settings window
+                        normal_key.action = Action::Settings;
+                        shift_key.action = Action::Settings;
+                        normal_key.name = "⚙".to_string();
+                        shift_key.name = "⚙".to_string();
+                        normal_key.icon = Some(SETTINGS_ICON);
+                        shift_key.icon = Some(SETTINGS_ICON);
+                    }
                     None => {
-                        eprintln!("cannot find keycode for {:?} in keymap", key);
+                        log::warn!("cannot find keycode for {:?} in keymap", key);
                     }
                 }
 
-                let name_width = match key {
-                    "BKSL" => {
-                        normal_key.width = 1.5;
-                        shift_key.width = 1.5;
-                        None
-                    }
-                    "BKSP" => Some(("Bksp", 2.0)),
-                    "DELE" => Some(("Del", 2.0)),
-                    "CAPS" => Some(("Caps", 1.75)),
-                    "ESC" => Some(("Esc", 1.0)),
-                    "LALT" => Some(("Alt", 1.25)),
-                    "LCTL" => Some(("Ctrl", 1.25)),
-                    "LFSH" => Some(("Shift", 2.25)),
-                    "LWIN" => Some(("Super", 1.25)),
-                    "PGDN" => Some(("PgDn", 1.0)),
-                    "PGUP" => Some(("PgUp", 1.0)),
-                    "RALT" => Some(("Alt", 1.25)),
-                    "RCTL" => Some(("Ctrl", 1.25)),
-                    "RTSH" => Some(("Shift", 1.75)),
-                    "RTRN" => Some(("Enter", 2.25)),
-                    "RWIN" => Some(("Super", 1.25)),
-                    "SPCE" => Some((" ", 5.5)),
-                    "TAB" => Some(("Tab", 1.5)),
-                    _ => None,
+                let width = match key {
+                    "BKSL" => 1.5,
+                    "BKSP" => 2.0,
+                    "DELE" => 2.0,
+                    "CAPS" => 1.75,
+                    "LALT" => 1.25,
+                    "LCTL" => 1.25,
+                    "LFSH" => 2.25,
+                    "LWIN" => 1.25,
+                    "RALT" => 1.25,
+                    "RTSH" => 1.75,
+                    "RTRN" => 2.25,
+                    "SPCE" => 5.0,
+                    "TAB" => 1.5,
+                    _ => 1.0,
                 };
-                if let Some((name, width)) = name_width {
-                    normal_key.name = name.to_string();
-                    normal_key.width = width;
-                    shift_key.name = name.to_string();
-                    shift_key.width = width;
-                }
+                normal_key.width = width;
+                shift_key.width = width;
 
                 normal_row.push(normal_key);
                 shift_row.push(shift_key);
@@ -145,8 +1097,128 @@ impl From<&xkb::Keymap> for Layout {
             normal_layer.rows.push(normal_row);
             shift_layer.rows.push(shift_row);
         }
+        // Function keys, digits, and letters are split in groups of about five keys
+        normal_layer.split_scan_groups(5.0);
+        shift_layer.split_scan_groups(5.0);
         Layout {
+            kind: LayoutKind::Full,
             layers: vec![normal_layer, shift_layer],
+            gesture_keys: GestureKeys::new(keymap),
+            classes: key_classes(keymap),
+            keysyms: key_keysyms(keymap, 0),
+            shift_keysyms: key_keysyms(keymap, 1),
+            rtl: is_rtl(keymap),
+        }
+        .mirror_rtl()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keymap(layout: &str) -> xkb::Keymap {
+        // Labels use the fallback language
+        crate::localize::localizer();
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        xkb::Keymap::new_from_names(
+            &context,
+            "evdev",
+            "pc105",
+            layout,
+            "",
+            None,
+            xkb::COMPILE_NO_FLAGS,
+        )
+        .unwrap_or_else(|| panic!("failed to load {layout} keymap"))
+    }
+
+    fn actions(layer: &Layer) -> Vec<Vec<Action>> {
+        layer
+            .rows
+            .iter()
+            .map(|keys| keys.iter().map(|key| key.action).collect())
+            .collect()
+    }
+
+    /// Keys of a layer in scanning order
+    fn scanned(layer: &Layer) -> Vec<Action> {
+        layer
+            .scan_groups
+            .iter()
+            .flatten()
+            .map(|&(row, column)| layer.rows[row][column].action)
+            .collect()
+    }
+
+    #[test]
+    fn rtl_keymaps_mirror_rows() {
+        let us = keymap("us");
+        for (layout, first_letter) in [("ara", "ض"), ("il", "/"), ("ir", "ض")] {
+            let keymap = keymap(layout);
+            assert!(is_rtl(&keymap), "{layout}");
+            for (rtl, ltr) in [
+                (Layout::from(&keymap), Layout::from(&us)),
+                (Layout::compact(&keymap), Layout::compact(&us)),
+            ] {
+                assert!(rtl.rtl && !ltr.rtl);
+                // Shifted and symbol keys depend on the keymap, unshifted keys do not
+                let (rtl_layer, ltr_layer) = (&rtl.layers[0], &ltr.layers[0]);
+                let mut mirrored = actions(ltr_layer);
+                for keys in mirrored.iter_mut() {
+                    keys.reverse();
+                }
+                assert_eq!(actions(rtl_layer), mirrored, "{layout} {:?}", rtl.kind);
+                // Scanning visits the same keys, starting from the right
+                assert_eq!(scanned(rtl_layer), scanned(ltr_layer), "{layout}");
+            }
+
+            // The key typing the first letter of the top letter row is on the right
+            let compact = Layout::compact(&keymap);
+            let top = &compact.layers[0].rows[0];
+            assert_eq!(top.last().unwrap().name, first_letter, "{layout}");
+            let backspace = compact.layers[0].rows[2].first().unwrap();
+            assert_eq!(compact.key_symbol(backspace), Some("⌦"), "{layout}");
+        }
+    }
+
+    #[test]
+    fn numpad_is_not_mirrored() {
+        let ltr = Layout::numpad(&keymap("us"));
+        let rtl = Layout::numpad(&keymap("ara"));
+        assert!(rtl.rtl);
+        assert_eq!(actions(&rtl.layers[0]), actions(&ltr.layers[0]));
+    }
+
+    #[test]
+    fn arabic_harakat_on_dotted_circle() {
+        let layout = Layout::from(&keymap("ara"));
+        let labels: Vec<&str> = layout.layers[1]
+            .rows
+            .iter()
+            .flatten()
+            .map(|key| key.name.as_str())
+            .collect();
+        // Fatha and kasra are typed with shift on the top letter row
+        assert!(labels.contains(&"\u{25CC}\u{064E}"), "{labels:?}");
+        assert!(labels.contains(&"\u{25CC}\u{0650}"), "{labels:?}");
+    }
+
+    #[test]
+    fn combining_marks() {
+        // Arabic fatha, Hebrew patah, Devanagari virama and vowel sign
+        for c in ['\u{064E}', '\u{05B7}', '\u{094D}', '\u{093E}'] {
+            assert!(is_combining(c), "{c:?}");
+        }
+        // Sinhala al-lakuna, and vowel signs outside of the Indic offsets
+        for c in ['\u{0DCA}', '\u{0DDA}', '\u{0DDF}', '\u{0DF2}'] {
+            assert!(is_combining(c), "{c:?}");
+        }
+        // Sinhala letters at the offsets of Indic signs
+        for c in ['a', 'ب', '\u{0D85}', '\u{0DBA}', '\u{0DC0}', '\u{0DC3}'] {
+            assert!(!is_combining(c), "{c:?}");
         }
+        assert_eq!(char_label('\u{0DCA}'), "\u{25CC}\u{0DCA}");
+        assert_eq!(char_label('\u{0DC3}'), "\u{0DC3}");
     }
 }
@@ -7,30 +7,75 @@ use cosmic::{
     cosmic_config::{self, CosmicConfigEntry},
     executor,
     iced::{
-        Length, Limits, Subscription,
-        futures::{self, sink::SinkExt},
+        Alignment, Length, Limits, Point, Size, Subscription,
+        event::{
+            self, PlatformSpecific,
+            wayland::{Event as WaylandEvent, OutputEvent},
+        },
+        futures::{self, channel::mpsc::UnboundedSender, sink::SinkExt},
         platform_specific::{
             runtime::wayland::layer_surface::{IcedMargin, IcedOutput, SctkLayerSurfaceSettings},
             shell::wayland::commands::layer_surface::{
                 Anchor, KeyboardInteractivity, Layer, destroy_layer_surface, get_layer_surface,
+                set_exclusive_zone, set_size,
             },
         },
         stream,
-        window::Id as WindowId,
+        widget::mouse_area,
+        window::{self, Id as WindowId},
     },
     style, widget,
 };
-use std::{any::TypeId, collections::HashMap};
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use xkbcommon::xkb;
+
+pub mod accessibility;
+
+pub mod appearance;
+
+use autotext::Autotext;
+pub mod autotext;
+
+use cli::Request;
+pub mod cli;
+
+use compose::Compose;
+pub mod compose;
 
 use config::{CONFIG_VERSION, Config};
 pub mod config;
 
-use layout::Layout;
+pub mod convert;
+
+pub mod dbus;
+
+use dictionary::UserDictionary;
+pub mod dictionary;
+
+pub mod emoji;
+
+pub mod ipc;
+
+use layout::{Layer as LayoutLayer, LayerSwitch, Layout, LayoutKind};
 pub mod layout;
 
 pub mod localize;
 
-use wayland::{VkEvent, VkState, vk_channels};
+pub mod predict;
+
+use scan::{ScanInput, ScanMode, Scanner};
+pub mod scan;
+
+pub mod settings;
+
+pub mod swipe;
+
+use wayland::{ChangeCause, ContentHint, Keysym, VkEvent, VkState, WlOutput, vk_channels};
 pub mod wayland;
 
 pub fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -38,6 +83,51 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     localize::localize();
 
+    let args = match cli::Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+    if args.list_layouts {
+        for kind in LayoutKind::ALL.iter() {
+            println!("{}", kind.name());
+        }
+        return Ok(());
+    }
+    if args.import_dictionary.is_some() || args.export_dictionary.is_some() {
+        let mut dictionary = UserDictionary::load();
+        if let Some(path) = &args.import_dictionary {
+            dictionary.import(path)?;
+            dictionary.save();
+        }
+        if let Some(path) = &args.export_dictionary {
+            dictionary.export(path)?;
+        }
+        return Ok(());
+    }
+
+    match ipc::send(&args.requests()) {
+        Ok(true) => {
+            if args.has_startup_options() {
+                log::warn!("keyboard is already running, ignoring startup options");
+            }
+            return Ok(());
+        }
+        Ok(false) => {}
+        Err(err) => log::warn!("failed to send request to running keyboard: {}", err),
+    }
+    if args.command == Some(Request::Hide) {
+        // Nothing to hide
+        return Ok(());
+    }
+
+    config::migrate(App::APP_ID);
     let (config_handler, config) = match cosmic_config::Config::new(App::APP_ID, CONFIG_VERSION) {
         Ok(config_handler) => {
             let config = Config::get_entry(&config_handler).unwrap_or_else(|(errs, config)| {
@@ -61,16 +151,36 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
     let flags = Flags {
         config_handler,
         config,
+        args,
     };
     cosmic::app::run::<App>(settings, flags)?;
 
     Ok(())
 }
 
+/// How long an emoji or suggestion must be held for its alternate action
+const LONG_PRESS: Duration = Duration::from_millis(500);
+
+/// Time between saves of the user dictionary while words are learned
+const DICTIONARY_SAVE: Duration = Duration::from_secs(30);
+
+/// How often to try again to take an input method held by another one
+const IM_RETRY: Duration = Duration::from_secs(10);
+
+/// How often dwell progress is updated
+const DWELL_TICK: Duration = Duration::from_millis(50);
+
+/// Size of icons on keys in logical pixels
+const KEY_ICON_SIZE: u16 = 20;
+
+/// Number of conversion candidates shown in the suggestion bar
+const CANDIDATES: usize = 5;
+
 #[derive(Clone, Debug)]
 pub struct Flags {
     config_handler: Option<cosmic_config::Config>,
     config: Config,
+    args: cli::Args,
 }
 
 #[allow(dead_code)]
@@ -80,10 +190,84 @@ pub enum Message {
         action: layout::Action,
         pressed: bool,
     },
+    EmojiCategory(emoji::Category),
+    EmojiClose,
+    EmojiCommit(String),
+    EmojiPress(String),
+    EmojiQuery(Option<String>),
+    EmojiRelease(String),
     Layer(usize),
-    Layout(Layout),
+    Layouts(Vec<Layout>),
+    Suggestion(String),
+    SuggestionPress(String),
+    PointerMove(Point),
+    PointerRelease,
+    PointerExit,
+    DwellTick,
+    SaveDictionary,
+    Typed(u32, Keysym),
     VkeTx(channel::Sender<VkEvent>),
     VkState(u32, VkState),
+    Request(Request),
+    Scan(ScanInput),
+    Config(Config),
+    SetConfig(Config),
+    CloseSettings,
+    DbusTx(UnboundedSender<dbus::Update>),
+    /// Another input method holds the input method of the seat
+    ImUnavailable(u32),
+    /// The input method of the seat was taken after it was unavailable
+    ImAvailable(u32),
+    RetryInputMethod,
+    Ime(dbus::Ime),
+    Output(OutputEvent, WlOutput),
+    WindowClosed(WindowId),
+}
+
+struct EmojiPicker {
+    category: emoji::Category,
+    /// Search query, if searching
+    query: Option<String>,
+    /// Emoji being held and when it was pressed
+    pressed: Option<(String, Instant)>,
+    /// Emoji showing skin tone variants
+    tones: Option<&'static emoji::Emoji>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum DragKind {
+    /// Move the cursor by dragging the space bar
+    Cursor,
+    /// Select words to delete by dragging backspace
+    DeleteWords,
+}
+
+struct Drag {
+    kind: DragKind,
+    /// Key where the drag started, released or typed if the pointer did not move
+    action: layout::Action,
+    path: Vec<Point>,
+    pressed_at: Instant,
+    /// Pointer moved far enough to send cursor keys
+    moved: bool,
+    /// Cursor steps sent, positive to the right
+    steps: i32,
+    /// Shift is held to select text
+    selecting: bool,
+}
+
+/// Key the pointer rests on in dwell mode
+struct Dwell {
+    pos: layout::KeyPos,
+    started: Instant,
+    /// Key was pressed, pressed again only if it repeats
+    triggered: bool,
+}
+
+struct Swipe {
+    /// Key where the swipe started, typed if the swipe was too short
+    action: layout::Action,
+    path: Vec<Point>,
 }
 
 pub struct App {
@@ -92,11 +276,1213 @@ pub struct App {
     config: Config,
     key_padding: usize,
     key_size: usize,
-    layout: Option<Layout>,
+    layouts: Vec<Layout>,
     layer: usize,
+    base_layer: usize,
+    /// Layer last announced to the screen reader
+    announced_layer: usize,
+    one_shot: bool,
+    /// Shift layer was enabled by auto-capitalization
+    auto_shift: bool,
+    numpad_docked: bool,
+    /// Layout requested from the command line or D-Bus for this session only
+    layout_override: Option<LayoutKind>,
+    emoji_picker: Option<EmojiPicker>,
+    model: predict::Model,
+    dictionary: UserDictionary,
+    suggestions: Vec<String>,
+    /// Suggestion being held and when it was pressed
+    suggestion_pressed: Option<(String, Instant)>,
+    autotext: Autotext,
+    /// Autotext edit waiting for the typed key that caused it to show in the text field
+    pending_replacement: Option<autotext::Replacement>,
+    /// Dead key or compose sequence being typed
+    compose: Compose,
+    /// Engine composing typed letters, its candidates are shown as suggestions
+    engine: Option<Box<dyn convert::Engine>>,
+    /// Where a dictionary of the engine can be added, shown in settings if it has none
+    missing_dictionary: Option<PathBuf>,
+    candidates: Vec<convert::Candidate>,
+    /// Key typed into the engine, its release is not sent either
+    converted_key: Option<layout::Action>,
+    /// Another input method took the input method of a seat first
+    im_unavailable: bool,
+    /// Candidates of the external input method, shown as suggestions
+    ime_candidates: Vec<String>,
+    /// Keys were typed since the last input method state update
+    typed_since_done: bool,
+    drag: Option<Drag>,
+    swipe: Option<Swipe>,
+    dwell: Option<Dwell>,
+    /// Word committed by the last swipe, replaced when picking an alternative
+    swipe_committed: Option<String>,
+    swipe_candidates: Vec<String>,
     surface_id: Option<WindowId>,
     vke_tx: Option<channel::Sender<VkEvent>>,
+    dbus_tx: Option<UnboundedSender<dbus::Update>>,
     vk_state: HashMap<u32, VkState>,
+    /// Key being held, shown enlarged as a preview
+    pressed_key: Option<layout::Action>,
+    settings_id: Option<WindowId>,
+    settings_labels: settings::Labels,
+    /// Languages with word lists, listed in settings
+    languages: Vec<String>,
+    args: cli::Args,
+    /// Shown, or waiting for a layout to be shown
+    visible: bool,
+    /// Outputs by connector name
+    outputs: HashMap<String, WlOutput>,
+    scanner: Scanner,
+}
+
+impl App {
+    /// State of the seat with an active input method, if any
+    fn active_vk_state(&self) -> Option<&VkState> {
+        self.vk_state.values().find(|vk_state| vk_state.active)
+    }
+
+    fn layout_by_kind(&self, kind: LayoutKind) -> Option<&Layout> {
+        self.layouts.iter().find(|layout| layout.kind == kind)
+    }
+
+    /// Layout requested for the session or selected in the config, falling
+    /// back to the first one available
+    fn layout(&self) -> Option<&Layout> {
+        let numeric = self.config.numpad_auto
+            && self
+                .active_vk_state()
+                .is_some_and(|vk_state| vk_state.is_numeric());
+        let kind = if numeric {
+            LayoutKind::Numpad
+        } else {
+            self.layout_override.unwrap_or(self.config.layout)
+        };
+        self.layout_by_kind(kind).or_else(|| self.layouts.first())
+    }
+
+    /// Numeric keypad docked beside the main layout, if enabled
+    fn docked_numpad(&self) -> Option<&Layout> {
+        if !self.numpad_docked || self.layout()?.kind == LayoutKind::Numpad {
+            return None;
+        }
+        self.layout_by_kind(LayoutKind::Numpad)
+    }
+
+    /// Numpad layers are indexed by the Num Lock state
+    fn numpad_layer(&self) -> usize {
+        if self
+            .active_vk_state()
+            .or_else(|| self.vk_state.values().next())
+            .is_some_and(|vk_state| vk_state.num_lock)
+        {
+            1
+        } else {
+            0
+        }
+    }
+
+    fn view_layer<'a>(
+        &'a self,
+        layout: &'a Layout,
+        layout_layer: &'a LayoutLayer,
+    ) -> Element<'a, Message> {
+        let high_contrast = self.high_contrast();
+        let scanning = self.config.scan_mode != ScanMode::Off;
+        // Only the main layout is scanned and dwelled on, not the docked numpad
+        let main = self.layout().is_some_and(|main| main.kind == layout.kind);
+        let scan_order = if main { self.scan_order() } else { Vec::new() };
+        let dwell = self.dwell_progress().filter(|_| main);
+        let mut grid = widget::column::with_capacity(layout_layer.rows.len());
+        for (row, layout_row) in layout_layer.rows.iter().enumerate() {
+            let mut r = widget::row::with_capacity(layout_row.len());
+            for (column, key) in layout_row.iter().enumerate() {
+                let size = (self.key_size as f32 * key.width, self.key_size as f32);
+                if key.action == layout::Action::Emoji && !self.emoji_available() {
+                    // Keep the gap so other keys do not move
+                    r = r.push(widget::Space::new(size.0, size.1));
+                    continue;
+                }
+                let icon = layout
+                    .key_icon(key)
+                    .filter(|_| self.config.key_icons)
+                    .map(|name| widget::icon::from_name(name).size(KEY_ICON_SIZE))
+                    .filter(|icon| icon.clone().path().is_some());
+                let label: Element<_> = if let Some(mut icon) = icon {
+                    if high_contrast {
+                        icon = icon
+                            .size((self.key_size as f32 * appearance::HIGH_CONTRAST_LABEL) as u16);
+                    }
+                    if self.preview(key) {
+                        icon = icon.size((self.key_size as f32 * 0.6) as u16);
+                    }
+                    icon.icon().into()
+                } else {
+                    // Without the icon in the theme, fall back to a symbol or the name
+                    let symbol = layout.key_symbol(key).filter(|_| self.config.key_icons);
+                    // Keys finishing a pending dead key show the composed character
+                    let name = self.compose.preview(&key.name).unwrap_or(&key.name);
+                    let mut label = widget::text(symbol.unwrap_or(name));
+                    if high_contrast {
+                        label = label
+                            .size(self.key_size as f32 * appearance::HIGH_CONTRAST_LABEL)
+                            .font(cosmic::font::bold());
+                    }
+                    if self.preview(key) {
+                        label = label.size(self.key_size as f32 * 0.6);
+                    }
+                    label.into()
+                };
+                let mut content: Element<_> = widget::container(label).center(Length::Fill).into();
+                if let Some((_, progress)) = dwell.filter(|(pos, _)| *pos == (row, column)) {
+                    content = cosmic::iced::widget::stack([
+                        content,
+                        widget::icon(appearance::dwell_ring(progress, high_contrast))
+                            .width(Length::Fill)
+                            .height(Length::Fill)
+                            .into(),
+                    ])
+                    .into();
+                }
+                let latched = self.latched(key);
+                let button = widget::button::custom(content)
+                    .name(accessibility::key_name(layout, key))
+                    .selected(latched)
+                    .class(appearance::key(
+                        layout.key_class(key),
+                        latched,
+                        high_contrast,
+                        self.scanner.is_highlighted(&scan_order, (row, column)),
+                    ));
+                // While scanning, a click on any key is a switch selecting the highlighted key
+                let button = if scanning {
+                    button.on_press(Message::Scan(ScanInput::Select))
+                } else {
+                    button
+                        .on_press_down(Message::Key {
+                            action: key.action,
+                            pressed: true,
+                        })
+                        .on_press(Message::Key {
+                            action: key.action,
+                            pressed: false,
+                        })
+                };
+                r = r.push(
+                    widget::container(button)
+                        .padding(self.key_padding as u16)
+                        .height(Length::Fixed(size.1))
+                        .width(Length::Fixed(size.0)),
+                );
+            }
+            grid = grid.push(r);
+        }
+        grid.into()
+    }
+
+    /// Index of the layer shown for a layout
+    fn layer_index(&self, layout: &Layout) -> usize {
+        if layout.kind == LayoutKind::Numpad {
+            self.numpad_layer()
+        } else {
+            self.layer
+        }
+    }
+
+    /// Groups of keys of the shown layer in scanning order
+    fn scan_order(&self) -> Vec<Vec<layout::KeyPos>> {
+        if self.config.scan_mode == ScanMode::Off {
+            return Vec::new();
+        }
+        self.layout()
+            .and_then(|layout| layout.layers.get(self.layer_index(layout)))
+            .map(|layer| layer.scan_order(self.config.scan_method))
+            .unwrap_or_default()
+    }
+
+    /// Key being dwelled on and progress from 0 to 1, until it is pressed
+    fn dwell_progress(&self) -> Option<(layout::KeyPos, f32)> {
+        let dwell = self.dwell.as_ref().filter(|dwell| !dwell.triggered)?;
+        let progress =
+            dwell.started.elapsed().as_secs_f32() * 1000.0 / self.config.dwell_time.max(1) as f32;
+        Some((dwell.pos, progress))
+    }
+
+    /// Press and release the key, as if clicked
+    fn press_key(&mut self, action: layout::Action) -> Task<Message> {
+        let press = self.update(Message::Key {
+            action,
+            pressed: true,
+        });
+        let release = self.update(Message::Key {
+            action,
+            pressed: false,
+        });
+        Task::batch([press, release])
+    }
+
+    /// Action of a key of the shown layer
+    fn key_action(&self, (row, column): layout::KeyPos) -> Option<layout::Action> {
+        let layout = self.layout()?;
+        let layer = layout.layers.get(self.layer_index(layout))?;
+        Some(layer.rows.get(row)?.get(column)?.action)
+    }
+
+    /// Track the key under the pointer, restarting the dwell time when it changes
+    fn update_dwell(&mut self, point: Point) {
+        // Gestures are started by pressing, not dwelling
+        if !self.config.dwell || self.drag.is_some() || self.swipe.is_some() {
+            self.dwell = None;
+            return;
+        }
+        let key_size = self.key_size as f32;
+        let pos = self
+            .layout()
+            .and_then(|layout| layout.layers.get(self.layer_index(layout)))
+            .and_then(|layer| layer.key_at(point.x / key_size, point.y / key_size))
+            .filter(|&pos| {
+                self.key_action(pos)
+                    .is_some_and(|action| action != layout::Action::None)
+            });
+        match pos {
+            Some(pos) if self.dwell.as_ref().is_some_and(|dwell| dwell.pos == pos) => {}
+            Some(pos) => {
+                self.dwell = Some(Dwell {
+                    pos,
+                    started: Instant::now(),
+                    triggered: false,
+                });
+            }
+            None => self.dwell = None,
+        }
+    }
+
+    /// Keysyms of the configured switch keys
+    fn switches(&self) -> Vec<(Keysym, ScanInput)> {
+        if self.config.scan_mode == ScanMode::Off {
+            return Vec::new();
+        }
+        let mut switches = Vec::new();
+        for (name, input) in [
+            (&self.config.scan_select_key, ScanInput::Select),
+            (&self.config.scan_step_key, ScanInput::Step),
+        ] {
+            if name.is_empty() {
+                continue;
+            }
+            let keysym = xkb::keysym_from_name(name, xkb::KEYSYM_CASE_INSENSITIVE);
+            if keysym == Keysym::NoSymbol {
+                log::warn!("unknown switch key {:?}", name);
+                continue;
+            }
+            switches.push((keysym, input));
+        }
+        switches
+    }
+
+    /// High contrast keys, from the config or the desktop theme
+    fn high_contrast(&self) -> bool {
+        self.config.high_contrast || cosmic::theme::active().cosmic().is_high_contrast
+    }
+
+    /// Layer switches for the current layer and the numpad key while docked
+    fn latched(&self, key: &layout::Key) -> bool {
+        // Dead keys stay highlighted until the sequence is finished
+        if self.compose.is_pending(&key.name) {
+            return true;
+        }
+        match key.action {
+            layout::Action::Layer(switch) => {
+                switch.layer() != layout::LAYER_NORMAL && switch.layer() == self.layer
+            }
+            layout::Action::Numpad => self.numpad_docked,
+            _ => false,
+        }
+    }
+
+    /// Pressed character keys are enlarged, except in private fields
+    fn preview(&self, key: &layout::Key) -> bool {
+        self.config.key_preview
+            && self.pressed_key == Some(key.action)
+            && matches!(
+                key.action,
+                layout::Action::Keycode(_) | layout::Action::Shifted { .. }
+            )
+            && key.name.chars().count() == 1
+            && !self.private()
+    }
+
+    fn save_config(&mut self) {
+        if let Some(ref config_handler) = self.config_handler {
+            if let Err(err) = self.config.write_entry(config_handler) {
+                log::error!("failed to save config: {}", err);
+            }
+        }
+    }
+
+    fn commit_emoji(&mut self, emoji: String) {
+        self.send_vk_event(VkEvent::CommitString(emoji.clone()));
+        if self.private() {
+            return;
+        }
+        self.config.emoji_recent.retain(|recent| recent != &emoji);
+        self.config.emoji_recent.insert(0, emoji);
+        self.config.emoji_recent.truncate(emoji::RECENT_MAX);
+        self.save_config();
+    }
+
+    /// Text typed in the focused field must not be shown, suggested or remembered
+    fn private(&self) -> bool {
+        self.active_vk_state()
+            .is_some_and(|vk_state| vk_state.is_sensitive())
+    }
+
+    /// Keys are only typed with the virtual keyboard, another input method
+    /// composes and commits text
+    fn external_ime(&self) -> bool {
+        self.config.external_ime || self.im_unavailable
+    }
+
+    /// Emoji are committed as text through the input method, which another
+    /// input method holds in external mode
+    fn emoji_available(&self) -> bool {
+        !self.external_ime()
+    }
+
+    /// Show the suggestion bar above text layouts when a model is available
+    fn suggestion_bar(&self) -> bool {
+        (self.external_ime()
+            || self.engine.is_some()
+            || (self.config.suggestions || self.config.swipe_typing) && !self.model.is_empty())
+            && self
+                .layout()
+                .is_some_and(|layout| layout.kind != LayoutKind::Numpad)
+    }
+
+    fn update_suggestions(&mut self) {
+        self.suggestions.clear();
+        self.candidates.clear();
+        if self.external_ime() {
+            self.suggestions = self.ime_candidates.clone();
+            return;
+        }
+        if let Some(engine) = self.engine.as_ref().filter(|engine| !engine.is_empty()) {
+            self.candidates = engine.candidates(CANDIDATES);
+            self.suggestions = self
+                .candidates
+                .iter()
+                .map(|candidate| candidate.text.clone())
+                .collect();
+            return;
+        }
+        // The bar stays visible but empty in private fields to avoid resizing
+        if !self.suggestion_bar() || self.private() {
+            return;
+        }
+        if self.swipe_committed.is_some() {
+            // Keep showing alternatives for the swiped word
+            self.suggestions = self.swipe_candidates.clone();
+            return;
+        }
+        let Some(surrounding_text) = self
+            .active_vk_state()
+            .and_then(|vk_state| vk_state.surrounding_text.as_ref())
+        else {
+            return;
+        };
+        let (previous, prefix) = predict::current_word(surrounding_text.before_cursor());
+        self.suggestions = self.model.suggest(previous, prefix, 3);
+    }
+
+    /// Show the shift layer at the start of sentences if the text field asks for it
+    fn update_auto_shift(&mut self) {
+        let capitalize = self.config.auto_capitalize
+            && self.active_vk_state().is_some_and(|vk_state| {
+                vk_state.hint.contains(ContentHint::AutoCapitalization)
+                    && vk_state
+                        .surrounding_text
+                        .as_ref()
+                        .is_none_or(|text| autotext::sentence_start(text.before_cursor()))
+            });
+        if capitalize {
+            if self.layer == layout::LAYER_NORMAL && self.base_layer == layout::LAYER_NORMAL {
+                self.layer = layout::LAYER_SHIFT;
+                self.one_shot = true;
+                self.auto_shift = true;
+            }
+        } else if self.auto_shift {
+            self.release_one_shot();
+        }
+    }
+
+    fn send_key(&mut self, action: layout::Action, pressed: bool) {
+        if pressed && self.convert_key(action) {
+            self.converted_key = Some(action);
+            return;
+        }
+        if !pressed && self.converted_key == Some(action) {
+            self.converted_key = None;
+            self.release_one_shot();
+            return;
+        }
+        match action {
+            layout::Action::Keycode(kc) => {
+                self.send_vk_event(VkEvent::Key(kc, pressed));
+                if !pressed {
+                    self.release_one_shot();
+                }
+            }
+            layout::Action::Shifted { keycode, shift } => {
+                if pressed {
+                    self.send_vk_event(VkEvent::Key(shift, true));
+                    self.send_vk_event(VkEvent::Key(keycode, true));
+                } else {
+                    self.send_vk_event(VkEvent::Key(keycode, false));
+                    self.send_vk_event(VkEvent::Key(shift, false));
+                    self.release_one_shot();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Type a key into the conversion engine, returning true if it was used
+    fn convert_key(&mut self, action: layout::Action) -> bool {
+        if self.private() || self.external_ime() {
+            return false;
+        }
+        let Some(keysym) = self
+            .layout()
+            .and_then(|layout| layout.action_keysym(action))
+        else {
+            return false;
+        };
+        let Some(engine) = &mut self.engine else {
+            return false;
+        };
+        let composing = !engine.is_empty();
+        match keysym {
+            Keysym::BackSpace if composing => {
+                engine.pop();
+            }
+            Keysym::space if composing && engine.space_selects() => {
+                let candidate = engine.candidates(1).into_iter().next();
+                self.commit_conversion(candidate);
+                return true;
+            }
+            Keysym::Return | Keysym::KP_Enter if composing => {
+                self.commit_conversion(None);
+                return true;
+            }
+            _ => match keysym.key_char() {
+                Some(c) if engine.push(c) => {}
+                Some(c) if composing && !c.is_control() => {
+                    // Committed after the composed text through the input method, as
+                    // keys are not ordered with text committed by the input method
+                    self.commit_conversion(None);
+                    self.send_vk_event(VkEvent::CommitString(c.to_string()));
+                    return true;
+                }
+                _ => {
+                    // Other keys are typed after the composed text
+                    if composing {
+                        self.commit_conversion(None);
+                    }
+                    return false;
+                }
+            },
+        }
+        self.update_preedit();
+        true
+    }
+
+    /// Show the text being composed in the text field
+    fn update_preedit(&mut self) {
+        let preedit = self
+            .engine
+            .as_ref()
+            .map(|engine| engine.preedit())
+            .unwrap_or_default();
+        self.send_vk_event(VkEvent::Preedit(preedit));
+        self.update_suggestions();
+    }
+
+    /// Commit a candidate, or the composed text as shown, and keep composing
+    /// the rest of the reading
+    fn commit_conversion(&mut self, candidate: Option<convert::Candidate>) {
+        let Some(engine) = &mut self.engine else {
+            return;
+        };
+        let text = match candidate {
+            Some(candidate) => {
+                engine.consume(&candidate);
+                candidate.text
+            }
+            None => {
+                let text = engine.preedit();
+                engine.reset();
+                text
+            }
+        };
+        let preedit = engine.preedit();
+        // Committing text also clears the preedit text
+        self.send_vk_event(VkEvent::CommitString(text));
+        if !preedit.is_empty() {
+            self.send_vk_event(VkEvent::Preedit(preedit));
+        }
+        self.update_suggestions();
+    }
+
+    fn drag_kind(&self, kc: wayland::Keycode) -> Option<DragKind> {
+        if !self.config.key_gestures {
+            return None;
+        }
+        let gesture_keys = self.layout()?.gesture_keys?;
+        if kc == gesture_keys.space {
+            Some(DragKind::Cursor)
+        } else if kc == gesture_keys.backspace {
+            Some(DragKind::DeleteWords)
+        } else {
+            None
+        }
+    }
+
+    fn tap_key(&self, kc: wayland::Keycode) {
+        self.send_vk_event(VkEvent::Key(kc, true));
+        self.send_vk_event(VkEvent::Key(kc, false));
+    }
+
+    /// Send cursor keys for the distance dragged, one per half key
+    fn update_drag(&mut self) {
+        let Some(gesture_keys) = self.layout().and_then(|layout| layout.gesture_keys) else {
+            return;
+        };
+        let step_size = self.key_size as f32 / 2.0;
+        let Some(drag) = &mut self.drag else {
+            return;
+        };
+        let (Some(first), Some(last)) = (drag.path.first(), drag.path.last()) else {
+            return;
+        };
+        let mut target = ((last.x - first.x) / step_size) as i32;
+        if drag.kind == DragKind::DeleteWords {
+            // Only select words to the left of the cursor
+            target = target.min(0);
+        }
+        if target == drag.steps {
+            return;
+        }
+
+        let mut events = Vec::new();
+        let mut release = None;
+        if !drag.moved {
+            drag.moved = true;
+            if drag.kind == DragKind::DeleteWords {
+                // Stop repeating Backspace, words are selected instead
+                release = Some(drag.action);
+            }
+            drag.selecting =
+                drag.kind == DragKind::DeleteWords || drag.pressed_at.elapsed() >= LONG_PRESS;
+            if drag.selecting {
+                events.push(VkEvent::Key(gesture_keys.shift, true));
+            }
+        }
+        let word = drag.kind == DragKind::DeleteWords;
+        if word {
+            events.push(VkEvent::Key(gesture_keys.ctrl, true));
+        }
+        while drag.steps != target {
+            let kc = if target > drag.steps {
+                drag.steps += 1;
+                gesture_keys.right
+            } else {
+                drag.steps -= 1;
+                gesture_keys.left
+            };
+            events.push(VkEvent::Key(kc, true));
+            events.push(VkEvent::Key(kc, false));
+        }
+        if word {
+            events.push(VkEvent::Key(gesture_keys.ctrl, false));
+        }
+        if let Some(action) = release {
+            self.send_key(action, false);
+        }
+        for event in events {
+            self.send_vk_event(event);
+        }
+    }
+
+    fn finish_drag(&mut self, drag: Drag) {
+        if !drag.moved {
+            if drag.kind == DragKind::Cursor {
+                self.send_key(drag.action, true);
+            }
+            self.send_key(drag.action, false);
+            return;
+        }
+        let Some(gesture_keys) = self.layout().and_then(|layout| layout.gesture_keys) else {
+            return;
+        };
+        if drag.selecting {
+            self.send_vk_event(VkEvent::Key(gesture_keys.shift, false));
+        }
+        if drag.kind == DragKind::DeleteWords && drag.steps != 0 {
+            self.tap_key(gesture_keys.backspace);
+        }
+    }
+
+    /// Swipe typing is used on text layouts when a word list is available
+    fn swipe_mode(&self) -> bool {
+        self.config.swipe_typing
+            && self.engine.is_none()
+            && !self.external_ime()
+            && !self.model.is_empty()
+            && !self.private()
+            && self
+                .layout()
+                .is_some_and(|layout| layout.kind != LayoutKind::Numpad)
+    }
+
+    /// Decode a swiped path and commit the best word, returning false if the
+    /// path was too short to be a swipe
+    fn finish_swipe(&mut self, path: &[Point]) -> bool {
+        let key_size = self.key_size as f32;
+        let path: Vec<swipe::Point> = path
+            .iter()
+            .map(|point| (point.x / key_size, point.y / key_size))
+            .collect();
+        if swipe::path_length(&path) < 1.0 {
+            return false;
+        }
+
+        let Some(layout_layer) = self
+            .layout()
+            .and_then(|layout| layout.layers.get(self.layer))
+        else {
+            return true;
+        };
+        let keys = swipe::key_centers(layout_layer);
+        let mut candidates = swipe::decode(&path, &keys, &self.model, 3);
+        if self.layer == layout::LAYER_SHIFT {
+            for candidate in candidates.iter_mut() {
+                let mut chars = candidate.chars();
+                *candidate = chars
+                    .next()
+                    .map(|c| c.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default();
+            }
+        }
+        self.release_one_shot();
+
+        if let Some(best) = candidates.first() {
+            self.send_vk_event(VkEvent::CommitString(format!("{} ", best)));
+            self.swipe_committed = Some(best.clone());
+            self.suggestions = candidates.clone();
+            self.swipe_candidates = candidates;
+        }
+        true
+    }
+
+    /// Record an accepted word, unless the text field is sensitive or
+    /// prediction is disabled
+    fn learn(&mut self, previous: Option<&str>, word: &str) {
+        if !(self.config.suggestions || self.config.autocorrect || self.config.swipe_typing)
+            || self.active_vk_state().is_none()
+            || self.private()
+        {
+            return;
+        }
+        self.dictionary.learn(&mut self.model, previous, word);
+    }
+
+    fn close_surface(&mut self) -> Task<Message> {
+        self.update_dbus();
+        self.dictionary.save_changes();
+        match self.surface_id.take() {
+            Some(surface_id) if self.args.windowed => window::close(surface_id),
+            Some(surface_id) => destroy_layer_surface(surface_id),
+            None => Task::none(),
+        }
+    }
+
+    /// Use a layout until the layout is changed in the settings
+    fn set_layout(&mut self, kind: LayoutKind) -> Task<Message> {
+        self.layout_override = Some(kind);
+        self.layer = 0;
+        self.base_layer = 0;
+        self.one_shot = false;
+        self.update_surface()
+    }
+
+    fn request(&mut self, request: Request) -> Task<Message> {
+        match request {
+            Request::Show => {
+                self.visible = true;
+                self.update_surface()
+            }
+            Request::Hide => {
+                self.visible = false;
+                self.close_surface()
+            }
+            Request::Toggle => self.request(if self.visible {
+                Request::Hide
+            } else {
+                Request::Show
+            }),
+            Request::Layout(kind) => self.set_layout(kind),
+            Request::Settings => self.open_settings(),
+        }
+    }
+
+    fn open_settings(&mut self) -> Task<Message> {
+        if self.settings_id.is_some() {
+            return Task::none();
+        }
+        self.languages = predict::languages();
+        let (settings_id, task) = window::open(window::Settings {
+            size: Size::new(480.0, 640.0),
+            ..Default::default()
+        });
+        self.settings_id = Some(settings_id);
+        task.discard()
+    }
+
+    /// Save config changed in settings and apply it
+    fn set_config(&mut self, config: Config) -> Task<Message> {
+        let old = std::mem::replace(&mut self.config, config);
+        self.save_config();
+        self.update_config(old)
+    }
+
+    /// Apply changes from the old config
+    fn update_config(&mut self, old: Config) -> Task<Message> {
+        let mut tasks = Vec::new();
+        if old.app_theme != self.config.app_theme {
+            tasks.push(cosmic::command::set_theme(self.config.app_theme.theme()));
+        }
+        if old.conversion != self.config.conversion {
+            if self
+                .engine
+                .as_ref()
+                .is_some_and(|engine| !engine.is_empty())
+            {
+                self.send_vk_event(VkEvent::Preedit(String::new()));
+            }
+            self.engine = self.config.conversion.engine();
+            self.missing_dictionary = self.config.conversion.missing_dictionary();
+            self.converted_key = None;
+        }
+        if old.external_ime != self.config.external_ime {
+            // Try again to take the input method when no longer told to leave it
+            self.im_unavailable = false;
+            self.ime_candidates.clear();
+            if !self.emoji_available() {
+                self.emoji_picker = None;
+            }
+            if let Some(engine) = &mut self.engine {
+                engine.reset();
+            }
+            if self.vke_tx.is_some() {
+                self.send_vk_event(VkEvent::InputMethod(!self.config.external_ime));
+            }
+            self.update_dbus_ime();
+        }
+        if old.languages() != self.config.languages() {
+            self.model = predict::Model::load(&self.config.languages());
+            self.dictionary.apply(&mut self.model);
+        }
+        if old.layout != self.config.layout {
+            self.layout_override = None;
+            self.layer = 0;
+            self.base_layer = 0;
+            self.one_shot = false;
+        }
+        self.key_size = self.config.key_size as usize;
+        self.scanner.reset();
+        if self.vke_tx.is_some()
+            && (old.scan_mode, &old.scan_select_key, &old.scan_step_key)
+                != (
+                    self.config.scan_mode,
+                    &self.config.scan_select_key,
+                    &self.config.scan_step_key,
+                )
+        {
+            self.send_vk_event(VkEvent::Switches(self.switches()));
+        }
+        self.update_suggestions();
+        self.update_auto_shift();
+        tasks.push(self.update_surface());
+        Task::batch(tasks)
+    }
+
+    fn view_settings(&self) -> Element<Message> {
+        let header = widget::row::with_capacity(2)
+            .push(widget::text::title4(fl!("settings")).width(Length::Fill))
+            .push(
+                widget::button::icon(widget::icon::from_name("window-close-symbolic"))
+                    .tooltip(fl!("close"))
+                    .on_press(Message::CloseSettings),
+            )
+            .align_y(Alignment::Center)
+            .padding([8, 16]);
+        widget::column::with_capacity(2)
+            .push(header)
+            .push(settings::view(
+                &self.config,
+                &self.settings_labels,
+                &self.languages,
+                self.missing_dictionary.as_deref(),
+            ))
+            .into()
+    }
+
+    fn send_vk_event(&self, event: VkEvent) {
+        match &self.vke_tx {
+            Some(vke_tx) => {
+                //TODO: run in task
+                vke_tx.send(event).unwrap();
+            }
+            None => {
+                log::warn!("no virtual keyboard event sender");
+            }
+        }
+    }
+
+    fn switch_layer(&mut self, switch: LayerSwitch, pressed: bool) {
+        self.auto_shift = false;
+        match switch {
+            LayerSwitch::Momentary(layer) => {
+                self.layer = if pressed { layer } else { self.base_layer };
+            }
+            LayerSwitch::Toggle(layer) => {
+                if pressed {
+                    self.base_layer = if self.base_layer == layer {
+                        layout::LAYER_NORMAL
+                    } else {
+                        layer
+                    };
+                    self.layer = self.base_layer;
+                    self.one_shot = false;
+                }
+            }
+            LayerSwitch::OneShot(layer) => {
+                if pressed {
+                    if self.base_layer == layer {
+                        // Unlock
+                        self.base_layer = layout::LAYER_NORMAL;
+                        self.layer = self.base_layer;
+                        self.one_shot = false;
+                    } else if self.layer == layer && self.one_shot {
+                        // Pressed twice, lock
+                        self.base_layer = layer;
+                        self.one_shot = false;
+                    } else {
+                        self.layer = layer;
+                        self.one_shot = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn view_suggestion_bar(&self, width: f32) -> Element<Message> {
+        let mut bar = widget::row::with_capacity(self.suggestions.len());
+        // The best suggestion is first in reading order
+        let rtl = self.layout().is_some_and(|layout| layout.rtl);
+        let suggestions: Vec<&String> = if rtl {
+            self.suggestions.iter().rev().collect()
+        } else {
+            self.suggestions.iter().collect()
+        };
+        for suggestion in suggestions {
+            bar = bar.push(
+                widget::container(
+                    widget::button::custom(
+                        widget::container(widget::text(suggestion)).center(Length::Fill),
+                    )
+                    .class(style::Button::MenuItem)
+                    .on_press_down(Message::SuggestionPress(suggestion.clone()))
+                    .on_press(Message::Suggestion(suggestion.clone())),
+                )
+                .padding(self.key_padding as u16)
+                .width(Length::Fill),
+            );
+        }
+        widget::container(bar)
+            .height(Length::Fixed(self.key_size as f32))
+            .width(Length::Fixed(width))
+            .into()
+    }
+
+    fn view_emoji_picker<'a>(&'a self, picker: &'a EmojiPicker) -> Element<'a, Message> {
+        let key_size = self.key_size as f32;
+        let columns = self
+            .layout()
+            .map_or(0, |layout| layout.width() as usize)
+            .max(8);
+        let button = |label: String, on_press: Message, on_release: Message| {
+            Element::from(
+                widget::container(
+                    widget::button::custom(
+                        widget::container(widget::text(label).size(key_size / 2.0))
+                            .center(Length::Fill),
+                    )
+                    .class(style::Button::MenuItem)
+                    .on_press_down(on_press)
+                    .on_press(on_release),
+                )
+                .padding(self.key_padding as u16)
+                .height(Length::Fixed(key_size))
+                .width(Length::Fixed(key_size)),
+            )
+        };
+        let emoji_button = |emoji: &str| {
+            button(
+                emoji.to_string(),
+                Message::EmojiPress(emoji.to_string()),
+                Message::EmojiRelease(emoji.to_string()),
+            )
+        };
+        let action_button =
+            |label: &str, message: Message| button(label.to_string(), message.clone(), message);
+
+        let mut header = widget::row::with_capacity(emoji::Category::ALL.len() + 2);
+        if let Some(tones) = picker.tones {
+            header = header.push(action_button(
+                &tones.emoji,
+                Message::EmojiCommit(tones.emoji.clone()),
+            ));
+            for tone in emoji::SKIN_TONES {
+                let emoji = tones.with_tone(tone);
+                header = header.push(action_button(&emoji, Message::EmojiCommit(emoji.clone())));
+            }
+        } else if let Some(query) = &picker.query {
+            header = header
+                .push(action_button("✕", Message::EmojiQuery(None)))
+                .push(
+                    widget::container(widget::text(query).size(key_size / 2.0))
+                        .center_y(Length::Fixed(key_size))
+                        .width(Length::Fill),
+                );
+        } else {
+            for &category in emoji::Category::ALL.iter() {
+                header = header.push(action_button(
+                    category.icon(),
+                    Message::EmojiCategory(category),
+                ));
+            }
+            header = header
+                .push(action_button(
+                    "🔍",
+                    Message::EmojiQuery(Some(String::new())),
+                ))
+                .push(action_button("ABC", Message::EmojiClose));
+        }
+
+        let mut column = widget::column::with_capacity(2).push(header);
+        if let Some(query) = &picker.query {
+            // Results fit in one row above a keyboard for typing the query
+            let mut results = widget::row::with_capacity(columns);
+            for e in emoji::search(query).into_iter().take(columns) {
+                results = results.push(emoji_button(&e.emoji));
+            }
+            column = column.push(results);
+
+            if let Some(layout_layer) = self
+                .layout()
+                .and_then(|layout| layout.layers.get(layout::LAYER_NORMAL))
+            {
+                for layout_row in layout_layer.rows.iter() {
+                    let mut r = widget::row::with_capacity(layout_row.len());
+                    for key in layout_row.iter() {
+                        let mut chars = key.name.chars();
+                        if let (Some(c), None) = (chars.next(), chars.next()) {
+                            if c.is_alphabetic() {
+                                r = r.push(action_button(
+                                    &key.name,
+                                    Message::EmojiQuery(Some(format!("{}{}", query, c))),
+                                ));
+                            }
+                        }
+                    }
+                    column = column.push(r);
+                }
+            }
+
+            let mut backspace = query.clone();
+            backspace.pop();
+            column = column.push(
+                widget::row::with_capacity(3)
+                    .push(action_button(
+                        "␣",
+                        Message::EmojiQuery(Some(format!("{} ", query))),
+                    ))
+                    .push(action_button("⌫", Message::EmojiQuery(Some(backspace))))
+                    .push(action_button("ABC", Message::EmojiClose)),
+            );
+        } else {
+            let emojis: Vec<&str> = match picker.category {
+                emoji::Category::Recent => self
+                    .config
+                    .emoji_recent
+                    .iter()
+                    .map(String::as_str)
+                    .collect(),
+                category => emoji::category(category)
+                    .map(|e| e.emoji.as_str())
+                    .collect(),
+            };
+            let mut grid = widget::column::with_capacity(emojis.len().div_ceil(columns));
+            for chunk in emojis.chunks(columns) {
+                let mut r = widget::row::with_capacity(chunk.len());
+                for emoji in chunk.iter() {
+                    r = r.push(emoji_button(emoji));
+                }
+                grid = grid.push(r);
+            }
+            column = column.push(widget::scrollable(grid).height(Length::Fill));
+        }
+        column.into()
+    }
+
+    fn release_one_shot(&mut self) {
+        self.auto_shift = false;
+        if self.one_shot {
+            self.layer = self.base_layer;
+            self.one_shot = false;
+        }
+    }
+
+    /// Width of the layout and the numpad docked beside it, in logical pixels
+    fn surface_width(&self, layout: &Layout) -> f32 {
+        let mut width = layout.width() * self.key_size as f32;
+        if let Some(numpad) = self.docked_numpad() {
+            width += (self.key_size / 2) as f32 + numpad.width() * self.key_size as f32;
+        }
+        width
+    }
+
+    /// Height of the keyboard surface in logical pixels
+    fn surface_height(&self, layout: &Layout) -> usize {
+        let mut rows = layout.rows();
+        if let Some(numpad) = self.docked_numpad() {
+            rows = rows.max(numpad.rows());
+        }
+        if self.suggestion_bar() {
+            rows += 1;
+        }
+        (self.key_size + self.key_padding * 2) * rows
+    }
+
+    /// Ask assistive technologies to present a message
+    fn announce(&self, text: String) {
+        if let Some(dbus_tx) = &self.dbus_tx {
+            dbus::announce(dbus_tx, text);
+        }
+    }
+
+    /// Announce the shown layer if it changed since the last announcement
+    fn announce_layer(&mut self) {
+        if self.layer == self.announced_layer {
+            return;
+        }
+        self.announced_layer = self.layer;
+        // Numpad layers follow Num Lock, which is announced as a key
+        if self
+            .layout()
+            .is_some_and(|layout| layout.kind != LayoutKind::Numpad)
+        {
+            self.announce(accessibility::layer_name(self.layer));
+        }
+    }
+
+    fn update_dbus_ime(&self) {
+        if let Some(dbus_tx) = &self.dbus_tx {
+            dbus::set_external_ime(dbus_tx, self.external_ime());
+        }
+    }
+
+    fn update_dbus(&self) {
+        let Some(dbus_tx) = &self.dbus_tx else {
+            return;
+        };
+        let layout = self.layout();
+        dbus::update(
+            dbus_tx,
+            dbus::Status {
+                visible: self.visible,
+                layout: layout.map_or("", |layout| layout.kind.name()).to_string(),
+                height: layout.map_or(0, |layout| self.surface_height(layout)) as u32,
+            },
+        );
+    }
+
+    fn update_surface(&mut self) -> Task<Message> {
+        self.update_dbus();
+        if !self.visible {
+            return Task::none();
+        }
+        let Some(layout) = self.layout() else {
+            return Task::none();
+        };
+        let height = self.surface_height(layout);
+
+        if self.args.windowed {
+            // Keys are laid out with their padding inside the key size
+            let size = Size::new(self.surface_width(layout), height as f32);
+            return match self.surface_id {
+                Some(surface_id) => window::resize(surface_id, size),
+                None => {
+                    let (surface_id, task) = window::open(window::Settings {
+                        size,
+                        ..Default::default()
+                    });
+                    self.surface_id = Some(surface_id);
+                    task.discard()
+                }
+            };
+        }
+
+        let output = match &self.args.output {
+            Some(name) => match self.outputs.get(name) {
+                Some(output) => IcedOutput::Output(output.clone()),
+                None => {
+                    log::info!("waiting for output {}", name);
+                    return Task::none();
+                }
+            },
+            None => IcedOutput::Active,
+        };
+
+        match self.surface_id {
+            Some(surface_id) => Task::batch([
+                set_size(surface_id, None, Some(height as u32)),
+                set_exclusive_zone(surface_id, height as i32),
+            ]),
+            None => {
+                let surface_id = WindowId::unique();
+                self.surface_id = Some(surface_id);
+                get_layer_surface(SctkLayerSurfaceSettings {
+                    id: surface_id,
+                    layer: Layer::Top,
+                    keyboard_interactivity: KeyboardInteractivity::None,
+                    pointer_interactivity: true,
+                    anchor: Anchor::BOTTOM | Anchor::LEFT | Anchor::RIGHT,
+                    output,
+                    namespace: "cosmic-osk".into(),
+                    size: Some((None, Some(height as u32))),
+                    margin: IcedMargin {
+                        top: 0,
+                        bottom: 0,
+                        left: 0,
+                        right: 0,
+                    },
+                    exclusive_zone: height as i32,
+                    size_limits: Limits::NONE.min_width(320.0).min_height(height as f32),
+                })
+            }
+        }
+    }
 }
 
 /// Implement [`cosmic::Application`] to integrate with COSMIC.
@@ -111,7 +1497,7 @@ impl Application for App {
     type Message = Message;
 
     /// The unique application ID to supply to the window manager.
-    const APP_ID: &'static str = "com.system76.CosmicEdit";
+    const APP_ID: &'static str = "com.system76.CosmicOSK";
 
     fn core(&self) -> &Core {
         &self.core
@@ -123,82 +1509,536 @@ impl Application for App {
 
     /// Creates the application, and optionally emits command on initialize.
     fn init(core: Core, flags: Self::Flags) -> (Self, Task<Self::Message>) {
-        let app = App {
+        let dictionary = UserDictionary::load();
+        let mut model = predict::Model::load(&flags.config.languages());
+        dictionary.apply(&mut model);
+
+        let mut app = App {
             core,
             config_handler: flags.config_handler,
-            config: flags.config,
             key_padding: 4,
-            key_size: 64,
+            key_size: flags.config.key_size as usize,
             layer: 0,
-            layout: None,
+            base_layer: 0,
+            announced_layer: 0,
+            one_shot: false,
+            auto_shift: false,
+            layouts: Vec::new(),
+            numpad_docked: flags.config.numpad_docked,
+            layout_override: None,
+            emoji_picker: None,
+            model,
+            dictionary,
+            suggestions: Vec::new(),
+            suggestion_pressed: None,
+            autotext: Autotext::default(),
+            pending_replacement: None,
+            compose: Compose::new(),
+            engine: flags.config.conversion.engine(),
+            missing_dictionary: flags.config.conversion.missing_dictionary(),
+            candidates: Vec::new(),
+            converted_key: None,
+            im_unavailable: false,
+            ime_candidates: Vec::new(),
+            typed_since_done: false,
+            drag: None,
+            swipe: None,
+            swipe_committed: None,
+            swipe_candidates: Vec::new(),
+            dwell: None,
+            scanner: Scanner::default(),
+            config: flags.config,
             surface_id: None,
             vke_tx: None,
+            dbus_tx: None,
             vk_state: HashMap::new(),
+            pressed_key: None,
+            settings_id: None,
+            settings_labels: settings::Labels::new(),
+            languages: Vec::new(),
+            visible: !flags.args.hidden,
+            outputs: HashMap::new(),
+            args: flags.args,
         };
 
-        (app, Task::none())
+        let mut tasks = Vec::new();
+        if let Some(kind) = app.args.layout {
+            tasks.push(app.set_layout(kind));
+        }
+        if app.args.settings {
+            tasks.push(app.open_settings());
+        }
+
+        (app, Task::batch(tasks))
     }
 
     fn update(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::Key { action, pressed } => {
+                self.pressed_key = pressed.then_some(action);
                 match action {
                     layout::Action::None => {}
-                    layout::Action::Keycode(kc) => {
-                        match &self.vke_tx {
-                            Some(vke_tx) => {
-                                //TODO: run in task
-                                vke_tx.send(VkEvent::Key(kc, pressed)).unwrap();
-                            }
-                            None => {
-                                log::warn!("no virtual keyboard event sender");
+                    layout::Action::Keycode(kc) if self.drag_kind(kc).is_some() => {
+                        if pressed {
+                            // Backspace is pressed right away so that holding it repeats.
+                            // Space is typed on release, as a long press on it selects text.
+                            if self.drag_kind(kc) == Some(DragKind::DeleteWords) {
+                                self.send_key(action, true);
                             }
+                            self.drag = self.drag_kind(kc).map(|kind| Drag {
+                                kind,
+                                action,
+                                path: Vec::new(),
+                                pressed_at: Instant::now(),
+                                moved: false,
+                                steps: 0,
+                                selecting: false,
+                            });
+                        } else if let Some(drag) = self.drag.take() {
+                            self.finish_drag(drag);
+                        }
+                    }
+                    layout::Action::Keycode(_) | layout::Action::Shifted { .. } => {
+                        // Only character keys start swipes, others are held and repeat
+                        let swipe_key = self.swipe_mode()
+                            && self.layout().is_some_and(|layout| {
+                                layout.action_class(action) == layout::KeyClass::Character
+                            });
+                        if !swipe_key {
+                            self.send_key(action, pressed);
+                        } else if pressed {
+                            // Keys are typed on release unless the press turns into a swipe
+                            self.swipe = Some(Swipe {
+                                action,
+                                path: Vec::new(),
+                            });
+                        } else if let Some(swipe) = self.swipe.take()
+                            && !self.finish_swipe(&swipe.path)
+                        {
+                            self.send_key(swipe.action, true);
+                            self.send_key(swipe.action, false);
+                        }
+                    }
+                    layout::Action::Layer(switch) => {
+                        self.switch_layer(switch, pressed);
+                    }
+                    layout::Action::Emoji => {
+                        if pressed && self.emoji_available() {
+                            self.emoji_picker = Some(EmojiPicker {
+                                category: if self.config.emoji_recent.is_empty() {
+                                    emoji::Category::Smileys
+                                } else {
+                                    emoji::Category::Recent
+                                },
+                                query: None,
+                                pressed: None,
+                                tones: None,
+                            });
+                        }
+                    }
+                    layout::Action::Numpad => {
+                        if pressed {
+                            self.numpad_docked = !self.numpad_docked;
+                            self.config.numpad_docked = self.numpad_docked;
+                            self.save_config();
+                            return self.update_surface();
                         }
                     }
+                    layout::Action::Settings => {
+                        if pressed {
+                            return self.open_settings();
+                        }
+                    }
+                }
+                self.announce_layer();
+            }
+            Message::EmojiCategory(category) => {
+                if let Some(picker) = &mut self.emoji_picker {
+                    picker.category = category;
                 }
             }
+            Message::EmojiClose => {
+                self.emoji_picker = None;
+            }
+            Message::EmojiCommit(emoji) => {
+                if let Some(picker) = &mut self.emoji_picker {
+                    picker.tones = None;
+                }
+                self.commit_emoji(emoji);
+            }
+            Message::EmojiPress(emoji) => {
+                if let Some(picker) = &mut self.emoji_picker {
+                    picker.pressed = Some((emoji, Instant::now()));
+                }
+            }
+            Message::EmojiQuery(query) => {
+                if let Some(picker) = &mut self.emoji_picker {
+                    picker.query = query;
+                    picker.tones = None;
+                }
+            }
+            Message::EmojiRelease(emoji) => {
+                let Some(picker) = &mut self.emoji_picker else {
+                    return Task::none();
+                };
+                let long_press = picker.pressed.take().is_some_and(|(pressed, time)| {
+                    pressed == emoji && time.elapsed() >= LONG_PRESS
+                });
+                picker.tones = None;
+                if long_press && let Some(e) = emoji::find(&emoji).filter(|e| e.tones) {
+                    picker.tones = Some(e);
+                    return Task::none();
+                }
+                self.commit_emoji(emoji);
+            }
             Message::Layer(layer) => {
+                if self
+                    .layout()
+                    .is_none_or(|layout| layer >= layout.layers.len())
+                {
+                    log::warn!("invalid layer {}", layer);
+                    return Task::none();
+                }
                 self.layer = layer;
+                self.base_layer = layer;
+                self.one_shot = false;
+                self.announce_layer();
             }
-            Message::Layout(layout) => {
-                let mut height = 0;
-                for layer in layout.layers.iter() {
-                    height = height.max((self.key_size + self.key_padding * 2) * layer.rows.len());
-                }
-
+            Message::Layouts(layouts) => {
+                self.layouts = layouts;
+                self.compose.reset();
                 self.layer = 0;
-                self.layout = Some(layout);
-
-                //TODO: destroy and recreate surface when layout changes?
-                if !self.surface_id.is_some() {
-                    let surface_id = WindowId::unique();
-                    self.surface_id = Some(surface_id);
-                    return get_layer_surface(SctkLayerSurfaceSettings {
-                        id: surface_id,
-                        layer: Layer::Top,
-                        keyboard_interactivity: KeyboardInteractivity::None,
-                        pointer_interactivity: true,
-                        anchor: Anchor::BOTTOM | Anchor::LEFT | Anchor::RIGHT,
-                        output: IcedOutput::Active,
-                        namespace: "cosmic-osk".into(),
-                        size: Some((None, Some(height as u32))),
-                        margin: IcedMargin {
-                            top: 0,
-                            bottom: 0,
-                            left: 0,
-                            right: 0,
-                        },
-                        exclusive_zone: height as i32,
-                        size_limits: Limits::NONE.min_width(320.0).min_height(height as f32),
+                self.base_layer = 0;
+                self.one_shot = false;
+                return self.update_surface();
+            }
+            Message::Suggestion(word) => {
+                if self.external_ime() {
+                    self.suggestion_pressed = None;
+                    if let Some(dbus_tx) = &self.dbus_tx
+                        && let Some(index) = self
+                            .ime_candidates
+                            .iter()
+                            .position(|candidate| candidate == &word)
+                    {
+                        dbus::select_candidate(dbus_tx, index);
+                    }
+                    return Task::none();
+                }
+                if let Some(candidate) = self
+                    .candidates
+                    .iter()
+                    .find(|candidate| candidate.text == word)
+                    .cloned()
+                {
+                    self.suggestion_pressed = None;
+                    self.commit_conversion(Some(candidate));
+                    return Task::none();
+                }
+                let long_press = self
+                    .suggestion_pressed
+                    .take()
+                    .is_some_and(|(pressed, time)| pressed == word && time.elapsed() >= LONG_PRESS);
+                if long_press {
+                    self.dictionary.forget(&mut self.model, &word);
+                    self.suggestions.retain(|suggestion| suggestion != &word);
+                    self.swipe_candidates.retain(|candidate| candidate != &word);
+                    return Task::none();
+                }
+                if let Some(committed) = self.swipe_committed.take() {
+                    // Replace the swiped word and its space
+                    self.send_vk_event(VkEvent::ReplaceText {
+                        before_length: committed.len() as u32 + 1,
+                        text: format!("{} ", word),
+                    });
+                    self.learn(None, &word);
+                    self.swipe_committed = Some(word);
+                    return Task::none();
+                }
+                let Some(surrounding_text) = self
+                    .active_vk_state()
+                    .and_then(|vk_state| vk_state.surrounding_text.as_ref())
+                else {
+                    return Task::none();
+                };
+                let (previous, prefix) = predict::current_word(surrounding_text.before_cursor());
+                let previous = previous.map(str::to_string);
+                self.send_vk_event(VkEvent::ReplaceText {
+                    before_length: prefix.len() as u32,
+                    text: format!("{} ", word),
+                });
+                self.autotext.reset();
+                self.learn(previous.as_deref(), &word);
+            }
+            Message::SuggestionPress(word) => {
+                self.suggestion_pressed = Some((word, Instant::now()));
+            }
+            Message::PointerMove(point) => {
+                if let Some(drag) = &mut self.drag {
+                    drag.path.push(point);
+                    self.update_drag();
+                } else if let Some(swipe) = &mut self.swipe {
+                    swipe.path.push(point);
+                }
+                self.update_dwell(point);
+            }
+            Message::PointerExit => {
+                self.dwell = None;
+            }
+            Message::SaveDictionary => {
+                self.dictionary.save_changes();
+            }
+            Message::DwellTick => {
+                let Some(dwell) = &mut self.dwell else {
+                    return Task::none();
+                };
+                if dwell.triggered
+                    || dwell.started.elapsed()
+                        < Duration::from_millis(self.config.dwell_time.into())
+                {
+                    return Task::none();
+                }
+                let pos = dwell.pos;
+                let Some(action) = self.key_action(pos) else {
+                    self.dwell = None;
+                    return Task::none();
+                };
+                let backspace = self
+                    .layout()
+                    .and_then(|layout| layout.action_keysym(action))
+                    == Some(Keysym::BackSpace);
+                let repeat = self.config.dwell_repeat && backspace;
+                let task = self.press_key(action);
+                // Other keys are pressed again only after the pointer leaves them
+                self.dwell = Some(Dwell {
+                    pos,
+                    started: Instant::now(),
+                    triggered: !repeat,
+                });
+                return task;
+            }
+            Message::PointerRelease => {
+                // Released outside of the key where the gesture started
+                if let Some(drag) = self.drag.take() {
+                    self.finish_drag(drag);
+                } else if let Some(swipe) = self.swipe.take() {
+                    self.finish_swipe(&swipe.path);
+                }
+            }
+            Message::Typed(_seat_id, keysym) => {
+                self.typed_since_done = true;
+                self.compose.typed(keysym, &self.layouts);
+                if !self.private()
+                    && let Some(text) = accessibility::keysym_name(keysym).or_else(|| {
+                        keysym
+                            .key_char()
+                            .filter(|c| !c.is_control())
+                            .map(String::from)
+                    })
+                {
+                    self.announce(text);
+                }
+                self.swipe_committed = None;
+                self.swipe_candidates.clear();
+                let free_text = self
+                    .active_vk_state()
+                    .is_some_and(|vk_state| vk_state.is_free_text());
+                let options = autotext::Options {
+                    autocorrect: self.config.autocorrect && free_text,
+                    double_space_period: self.config.double_space_period && free_text,
+                };
+                self.pending_replacement = self.autotext.typed(keysym, options, &self.model);
+                let surrounding_text = self
+                    .active_vk_state()
+                    .is_some_and(|vk_state| vk_state.surrounding_text.is_some());
+                if !surrounding_text && let Some(replacement) = self.pending_replacement.take() {
+                    // Without surrounding text the typed key cannot be waited for
+                    self.send_vk_event(VkEvent::ReplaceText {
+                        before_length: replacement.before_length(),
+                        text: replacement.text,
                     });
                 }
+                if let Some((previous, word)) = self.autotext.take_completed() {
+                    self.learn(previous.as_deref(), &word);
+                }
             }
             Message::VkeTx(vke_tx) => {
                 self.vke_tx = Some(vke_tx);
+                self.send_vk_event(VkEvent::Switches(self.switches()));
+            }
+            Message::Request(request) => {
+                return self.request(request);
             }
+            Message::Scan(input) => {
+                if self.config.scan_mode == ScanMode::Off || !self.visible {
+                    return Task::none();
+                }
+                // The emoji picker is not scanned, a switch goes back to the keys
+                if self.emoji_picker.is_some() {
+                    if input == ScanInput::Select {
+                        self.emoji_picker = None;
+                    }
+                    return Task::none();
+                }
+                let order = self.scan_order();
+                match input {
+                    ScanInput::Step => self.scanner.step(&order),
+                    ScanInput::Select => {
+                        if let Some(action) = self
+                            .scanner
+                            .select(&order)
+                            .and_then(|pos| self.key_action(pos))
+                        {
+                            return self.press_key(action);
+                        }
+                    }
+                }
+            }
+            Message::Output(event, output) => match event {
+                OutputEvent::Created(Some(info)) | OutputEvent::InfoUpdate(info) => {
+                    if let Some(name) = info.name {
+                        let requested = self.args.output.as_ref() == Some(&name);
+                        self.outputs.insert(name, output);
+                        if requested && self.surface_id.is_none() {
+                            return self.update_surface();
+                        }
+                    }
+                }
+                OutputEvent::Removed => {
+                    let requested = self
+                        .args
+                        .output
+                        .as_ref()
+                        .is_some_and(|name| self.outputs.get(name) == Some(&output));
+                    self.outputs.retain(|_, known| known != &output);
+                    if requested {
+                        // Shown again when the output comes back
+                        return self.close_surface();
+                    }
+                }
+                _ => {}
+            },
+            Message::Config(config) => {
+                if config != self.config {
+                    log::info!("update config");
+                    let old = std::mem::replace(&mut self.config, config);
+                    return self.update_config(old);
+                }
+            }
+            Message::SetConfig(config) => {
+                return self.set_config(config);
+            }
+            Message::CloseSettings => {
+                if let Some(settings_id) = self.settings_id.take() {
+                    return window::close(settings_id);
+                }
+            }
+            Message::WindowClosed(id) => {
+                if self.settings_id == Some(id) {
+                    self.settings_id = None;
+                }
+                if self.surface_id == Some(id) {
+                    self.surface_id = None;
+                    self.visible = false;
+                    self.update_dbus();
+                }
+            }
+            Message::DbusTx(dbus_tx) => {
+                self.dbus_tx = Some(dbus_tx);
+                self.update_dbus();
+                self.update_dbus_ime();
+            }
+            Message::ImUnavailable(seat_id) => {
+                if self.im_unavailable || self.config.external_ime {
+                    return Task::none();
+                }
+                log::info!(
+                    "seat {} has another input method, only using the virtual keyboard",
+                    seat_id
+                );
+                self.im_unavailable = true;
+                self.emoji_picker = None;
+                self.update_dbus_ime();
+                self.update_suggestions();
+                return self.update_surface();
+            }
+            Message::ImAvailable(seat_id) => {
+                if !self.im_unavailable || self.config.external_ime {
+                    return Task::none();
+                }
+                log::info!("seat {} input method is available again", seat_id);
+                self.im_unavailable = false;
+                self.update_dbus_ime();
+                self.update_suggestions();
+                return self.update_surface();
+            }
+            Message::RetryInputMethod => {
+                if self.im_unavailable && !self.config.external_ime {
+                    self.send_vk_event(VkEvent::InputMethod(true));
+                }
+            }
+            Message::Ime(ime) => match ime {
+                dbus::Ime::Show | dbus::Ime::Hide if !self.config.auto_show => {}
+                dbus::Ime::Show => return self.request(Request::Show),
+                dbus::Ime::Hide => return self.request(Request::Hide),
+                dbus::Ime::Candidates(candidates) => {
+                    self.ime_candidates = candidates;
+                    self.update_suggestions();
+                }
+            },
             Message::VkState(seat_id, vk_state) => {
-                eprintln!("{}: {:?}", seat_id, vk_state);
-                self.vk_state.insert(seat_id, vk_state);
+                log::debug!(target: "vk", "seat {}: {:?}", seat_id, vk_state);
+                let kind = self.layout().map(|layout| layout.kind);
+                if vk_state.change_cause == ChangeCause::Other && !self.typed_since_done
+                    || !vk_state.active
+                {
+                    // Text was changed by something other than the keyboard
+                    self.autotext.reset();
+                    self.pending_replacement = None;
+                }
+                // The typed key was sent on the virtual keyboard, which is not ordered
+                // with the input method, so replace text once the key shows in the field
+                if let Some(replacement) = self.pending_replacement.take() {
+                    let typed = vk_state
+                        .surrounding_text
+                        .as_ref()
+                        .is_some_and(|text| text.before_cursor().ends_with(&replacement.replaced));
+                    if typed {
+                        self.send_vk_event(VkEvent::ReplaceText {
+                            before_length: replacement.before_length(),
+                            text: replacement.text,
+                        });
+                    } else {
+                        self.pending_replacement = Some(replacement);
+                    }
+                }
+                if !vk_state.active {
+                    self.compose.reset();
+                    if let Some(engine) = &mut self.engine {
+                        engine.reset();
+                    }
+                }
+                self.typed_since_done = false;
+                let active = vk_state.active;
+                let was_active = self
+                    .vk_state
+                    .insert(seat_id, vk_state)
+                    .is_some_and(|old| old.active);
+                self.update_suggestions();
+                self.update_auto_shift();
+                let mut tasks = Vec::new();
+                if self.config.auto_show && active != was_active {
+                    tasks.push(self.request(if active { Request::Show } else { Request::Hide }));
+                }
+                if self.layout().map(|layout| layout.kind) != kind {
+                    // Content purpose switched between text and numeric input
+                    self.layer = 0;
+                    self.base_layer = 0;
+                    self.one_shot = false;
+                    tasks.push(self.update_surface());
+                }
+                self.announce_layer();
+                return Task::batch(tasks);
             }
         }
 
@@ -210,56 +2050,70 @@ impl Application for App {
     }
 
     fn view_window(&self, id: WindowId) -> Element<Message> {
-        let element: Element<_> = if let Some(layout_layer) = self
-            .layout
-            .as_ref()
-            .and_then(|layout| layout.layers.get(self.layer))
-        {
-            let mut grid = widget::column::with_capacity(layout_layer.rows.len());
-            for layout_row in layout_layer.rows.iter() {
-                let mut r = widget::row::with_capacity(layout_row.len());
-                for key in layout_row.iter() {
-                    r = r.push(
-                        widget::container(
-                            widget::button::custom(
-                                widget::container(widget::text(&key.name)).center(Length::Fill),
-                            )
-                            //TODO: use custom style?
-                            .class(style::Button::MenuItem)
-                            .on_press_down(Message::Key {
-                                action: key.action,
-                                pressed: true,
-                            })
-                            .on_press(Message::Key {
-                                action: key.action,
-                                pressed: false,
-                            }),
-                        )
-                        .padding(self.key_padding as u16)
-                        .height(Length::Fixed(self.key_size as f32))
-                        .width(Length::Fixed(self.key_size as f32 * key.width)),
-                    );
+        let element: Element<_> = if self.settings_id == Some(id) {
+            self.view_settings()
+        } else if let Some(picker) = &self.emoji_picker {
+            self.view_emoji_picker(picker)
+        } else if let Some(layout) = self.layout() {
+            let layer = self.layer_index(layout);
+            match layout.layers.get(layer) {
+                Some(layout_layer) => {
+                    let mut main = mouse_area(self.view_layer(layout, layout_layer))
+                        .on_move(Message::PointerMove)
+                        .on_release(Message::PointerRelease)
+                        .on_exit(Message::PointerExit);
+                    if self.config.scan_mode == ScanMode::Step {
+                        main = main.on_right_press(Message::Scan(ScanInput::Step));
+                    }
+                    let keys: Element<_> = match self
+                        .docked_numpad()
+                        .and_then(|numpad| Some((numpad, numpad.layers.get(self.numpad_layer())?)))
+                    {
+                        Some((numpad, numpad_layer)) => widget::row::with_capacity(2)
+                            .push(main)
+                            .push(self.view_layer(numpad, numpad_layer))
+                            .spacing(self.key_size as u16 / 2)
+                            .into(),
+                        None => main.into(),
+                    };
+                    if self.suggestion_bar() {
+                        widget::column::with_capacity(2)
+                            .push(self.view_suggestion_bar(self.surface_width(layout)))
+                            .push(keys)
+                            .into()
+                    } else {
+                        keys
+                    }
                 }
-                grid = grid.push(r);
+                None => widget::text(format!("missing layer {}", layer)).into(),
             }
-            grid.into()
         } else {
             widget::text(format!("missing layout")).into()
         };
         widget::container(element)
-            .class(style::Container::Background)
+            .class(if self.settings_id == Some(id) {
+                style::Container::Background
+            } else {
+                appearance::background(self.high_contrast())
+            })
             .center(Length::Fill)
             .into()
     }
 
     fn subscription(&self) -> Subscription<Message> {
         struct VkSubscription;
-        Subscription::run_with_id(
+        struct ConfigSubscription;
+        struct DbusSubscription;
+        struct IpcSubscription;
+        let dump_keymap = self.args.dump_keymap;
+        let seat_name = self.args.seat.clone();
+        let input_method = !self.config.external_ime;
+        let mut subscriptions = vec![Subscription::run_with_id(
             TypeId::of::<VkSubscription>(),
             stream::channel(100, |mut output| async move {
                 //TODO: can this be made simpler?
                 tokio::task::spawn_blocking(move || {
-                    let (vke_tx, msg_rx) = vk_channels();
+                    let (vke_tx, msg_rx) = vk_channels(dump_keymap, seat_name, input_method);
                     futures::executor::block_on(async {
                         output.send(Message::VkeTx(vke_tx)).await
                     })
@@ -272,6 +2126,100 @@ impl Application for App {
                 .await
                 .unwrap()
             }),
-        )
+        )];
+
+        subscriptions.push(Subscription::run_with_id(
+            TypeId::of::<IpcSubscription>(),
+            stream::channel(16, |mut output| async move {
+                tokio::task::spawn_blocking(move || {
+                    let listener = match ipc::listen() {
+                        Ok(listener) => listener,
+                        Err(err) => {
+                            log::warn!("failed to listen for requests: {}", err);
+                            return;
+                        }
+                    };
+                    for stream in listener.incoming() {
+                        let stream = match stream {
+                            Ok(stream) => stream,
+                            Err(err) => {
+                                log::warn!("failed to accept request: {}", err);
+                                continue;
+                            }
+                        };
+                        for request in ipc::read_requests(stream) {
+                            futures::executor::block_on(async {
+                                output.send(Message::Request(request)).await
+                            })
+                            .unwrap();
+                        }
+                    }
+                })
+                .await
+                .unwrap()
+            }),
+        ));
+
+        subscriptions.push(
+            cosmic_config::config_subscription(
+                TypeId::of::<ConfigSubscription>(),
+                Self::APP_ID.into(),
+                CONFIG_VERSION,
+            )
+            .map(|update: cosmic_config::Update<Config>| {
+                if !update.errors.is_empty() {
+                    log::info!(
+                        "errors loading config {:?}: {:?}",
+                        update.keys,
+                        update.errors
+                    );
+                }
+                Message::Config(update.config)
+            }),
+        );
+
+        subscriptions.push(Subscription::run_with_id(
+            TypeId::of::<DbusSubscription>(),
+            stream::channel(16, |output| async move {
+                if let Err(err) = dbus::run(output).await {
+                    log::warn!("failed to run D-Bus service: {}", err);
+                }
+            }),
+        ));
+
+        if self.args.output.is_some() {
+            subscriptions.push(event::listen_with(|event, _, _| match event {
+                cosmic::iced::Event::PlatformSpecific(PlatformSpecific::Wayland(
+                    WaylandEvent::Output(event, output),
+                )) => Some(Message::Output(event, output)),
+                _ => None,
+            }));
+        }
+
+        if self.config.scan_mode == ScanMode::Auto && self.visible {
+            subscriptions.push(
+                cosmic::iced::time::every(Duration::from_millis(self.config.scan_interval.into()))
+                    .map(|_| Message::Scan(ScanInput::Step)),
+            );
+        }
+
+        if self.dwell.as_ref().is_some_and(|dwell| !dwell.triggered) {
+            subscriptions.push(cosmic::iced::time::every(DWELL_TICK).map(|_| Message::DwellTick));
+        }
+
+        // The other input method may go away without notice
+        if self.im_unavailable && !self.config.external_ime {
+            subscriptions
+                .push(cosmic::iced::time::every(IM_RETRY).map(|_| Message::RetryInputMethod));
+        }
+
+        if self.dictionary.has_changes() {
+            subscriptions
+                .push(cosmic::iced::time::every(DICTIONARY_SAVE).map(|_| Message::SaveDictionary));
+        }
+
+        subscriptions.push(window::close_events().map(Message::WindowClosed));
+
+        Subscription::batch(subscriptions)
     }
 }
@@ -52,10 +52,18 @@ pub fn localize() {
     let requested_languages = i18n_embed::DesktopLanguageRequester::requested_languages();
 
     if let Err(error) = localizer.select(&requested_languages) {
-        eprintln!("Error while loading language for App List {}", error);
+        log::warn!("failed to load language: {}", error);
     }
 }
 
+/// Language subtag of the current localization, such as `en`
+pub fn language() -> String {
+    LANGUAGE_LOADER
+        .get()
+        .map(|loader| loader.current_language().language.as_str().to_string())
+        .unwrap_or_else(|| "en".to_string())
+}
+
 pub fn sorter() -> &'static Collator {
     LANGUAGE_SORTER.get_or_init(|| {
         let mut options = CollatorOptions::new();
@@ -0,0 +1,409 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    collections::HashMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// XDG data directories, most important first
+pub fn data_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    match env::var_os("XDG_DATA_HOME") {
+        Some(data_home) if !data_home.is_empty() => dirs.push(PathBuf::from(data_home)),
+        _ => {
+            if let Some(home) = env::var_os("HOME") {
+                dirs.push(Path::new(&home).join(".local/share"));
+            }
+        }
+    }
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|data_dirs| !data_dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    for data_dir in data_dirs.split(':') {
+        if !data_dir.is_empty() {
+            dirs.push(PathBuf::from(data_dir));
+        }
+    }
+    dirs
+}
+
+/// Languages with n-gram files in the XDG data directories
+pub fn languages() -> Vec<String> {
+    let mut languages = Vec::new();
+    for data_dir in data_dirs().iter() {
+        let Ok(entries) = fs::read_dir(data_dir.join("cosmic-osk/ngrams")) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|extension| extension == "txt")
+                && let Some(language) = path.file_stem().and_then(|stem| stem.to_str())
+                && !languages.iter().any(|known| known == language)
+            {
+                languages.push(language.to_string());
+            }
+        }
+    }
+    languages.sort();
+    languages
+}
+
+/// Word and word pair counts, loaded from `cosmic-osk/ngrams/<language>.txt`
+/// in the XDG data directories. Each line holds one or two words followed by
+/// a count, for example `the 5000` or `of the 1200`.
+#[derive(Debug, Default)]
+pub struct Model {
+    unigrams: HashMap<String, u64>,
+    bigrams: HashMap<String, HashMap<String, u64>>,
+}
+
+impl Model {
+    /// Load and merge the n-gram files for the given languages
+    pub fn load(languages: &[String]) -> Self {
+        let mut model = Self::default();
+        for language in languages.iter() {
+            for data_dir in data_dirs().iter().rev() {
+                let path = data_dir
+                    .join("cosmic-osk/ngrams")
+                    .join(format!("{}.txt", language));
+                match model.load_file(&path) {
+                    Ok(()) => log::info!("loaded n-grams from {}", path.display()),
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                    Err(err) => {
+                        log::warn!("failed to load n-grams from {}: {}", path.display(), err)
+                    }
+                }
+            }
+        }
+        model
+    }
+
+    pub fn load_file(&mut self, path: &Path) -> io::Result<()> {
+        let data = fs::read_to_string(path)?;
+        self.parse(&data);
+        Ok(())
+    }
+
+    pub fn parse(&mut self, data: &str) {
+        for line in data.lines() {
+            if line.starts_with('#') {
+                continue;
+            }
+            let mut words: Vec<&str> = line.split_whitespace().collect();
+            let Some(count) = words.pop().and_then(|count| count.parse::<u64>().ok()) else {
+                continue;
+            };
+            match words[..] {
+                [word] => self.add_unigram(word, count),
+                [previous, word] => self.add_bigram(previous, word, count),
+                _ => {}
+            }
+        }
+    }
+
+    /// Write counts in the same format that is parsed, sorted for stable output
+    pub fn write<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut unigrams: Vec<_> = self.unigrams.iter().collect();
+        unigrams.sort();
+        for (word, count) in unigrams {
+            writeln!(writer, "{} {}", word, count)?;
+        }
+        let mut bigrams: Vec<_> = self
+            .bigrams
+            .iter()
+            .flat_map(|(previous, followers)| {
+                followers
+                    .iter()
+                    .map(move |(word, count)| (previous, word, count))
+            })
+            .collect();
+        bigrams.sort();
+        for (previous, word, count) in bigrams {
+            writeln!(writer, "{} {} {}", previous, word, count)?;
+        }
+        Ok(())
+    }
+
+    /// Add all counts from another model
+    pub fn merge(&mut self, other: &Model) {
+        for (word, &count) in other.unigrams.iter() {
+            self.add_unigram(word, count);
+        }
+        for (previous, followers) in other.bigrams.iter() {
+            for (word, &count) in followers.iter() {
+                self.add_bigram(previous, word, count);
+            }
+        }
+    }
+
+    /// Remove a word and all pairs containing it
+    pub fn remove(&mut self, word: &str) {
+        let word = word.to_lowercase();
+        self.unigrams.remove(&word);
+        self.bigrams.remove(&word);
+        self.bigrams.retain(|_, followers| {
+            followers.remove(&word);
+            !followers.is_empty()
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.unigrams.is_empty()
+    }
+
+    /// Known words with their counts
+    pub fn words(&self) -> impl Iterator<Item = (&str, u64)> {
+        self.unigrams
+            .iter()
+            .map(|(word, &count)| (word.as_str(), count))
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.unigrams.contains_key(&word.to_lowercase())
+    }
+
+    /// Most frequent known word one edit away from an unknown word
+    pub fn correction(&self, word: &str) -> Option<String> {
+        let lower = word.to_lowercase();
+        if lower.chars().count() < 3 || self.unigrams.contains_key(&lower) {
+            return None;
+        }
+        let chars: Vec<char> = lower.chars().collect();
+        let (best, _) = self
+            .unigrams
+            .iter()
+            .filter(|(candidate, _)| {
+                let candidate: Vec<char> = candidate.chars().collect();
+                edit_distance_one(&chars, &candidate)
+            })
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))?;
+        Some(match_case(word, best))
+    }
+
+    pub fn add_unigram(&mut self, word: &str, count: u64) {
+        *self.unigrams.entry(word.to_lowercase()).or_default() += count;
+    }
+
+    pub fn add_bigram(&mut self, previous: &str, word: &str, count: u64) {
+        *self
+            .bigrams
+            .entry(previous.to_lowercase())
+            .or_default()
+            .entry(word.to_lowercase())
+            .or_default() += count;
+    }
+
+    /// Completions of the prefix, or next words if the prefix is empty
+    pub fn suggest(&self, previous: Option<&str>, prefix: &str, limit: usize) -> Vec<String> {
+        let prefix_lower = prefix.to_lowercase();
+        let followers = previous.and_then(|previous| self.bigrams.get(&previous.to_lowercase()));
+
+        let mut candidates: Vec<(u64, &str)> = if prefix.is_empty() {
+            followers
+                .into_iter()
+                .flatten()
+                .map(|(word, &count)| (count, word.as_str()))
+                .collect()
+        } else {
+            self.unigrams
+                .iter()
+                .filter(|(word, _)| word.starts_with(&prefix_lower))
+                .map(|(word, &count)| {
+                    // Words seen after the previous word rank above all others
+                    let bigram = followers
+                        .and_then(|followers| followers.get(word))
+                        .copied()
+                        .unwrap_or(0);
+                    (
+                        bigram.saturating_mul(1 << 20).saturating_add(count),
+                        word.as_str(),
+                    )
+                })
+                .collect()
+        };
+        candidates.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+
+        candidates
+            .into_iter()
+            .take(limit)
+            .map(|(_, word)| match_case(prefix, word))
+            .collect()
+    }
+}
+
+/// Capitalize a lowercase word if the typed text starts with an uppercase letter
+fn match_case(typed: &str, word: &str) -> String {
+    if typed.chars().next().is_some_and(char::is_uppercase) {
+        let mut chars = word.chars();
+        chars
+            .next()
+            .map(|c| c.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    } else {
+        word.to_string()
+    }
+}
+
+/// Returns true if the words differ by exactly one insertion, deletion,
+/// substitution, or transposition of adjacent characters
+fn edit_distance_one(a: &[char], b: &[char]) -> bool {
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    let prefix = short
+        .iter()
+        .zip(long.iter())
+        .take_while(|(x, y)| x == y)
+        .count();
+    match long.len() - short.len() {
+        0 => {
+            if prefix == short.len() {
+                return false;
+            }
+            // Substitution
+            short[prefix + 1..] == long[prefix + 1..]
+                // Transposition
+                || (prefix + 1 < short.len()
+                    && short[prefix] == long[prefix + 1]
+                    && short[prefix + 1] == long[prefix]
+                    && short[prefix + 2..] == long[prefix + 2..])
+        }
+        1 => short[prefix..] == long[prefix + 1..],
+        _ => false,
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '\'' || c == '’'
+}
+
+/// Split text before the cursor into the previous word, if in the same
+/// sentence, and the partial word being typed
+pub fn current_word(before_cursor: &str) -> (Option<&str>, &str) {
+    let prefix_start = before_cursor
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_word_char(c))
+        .last()
+        .map_or(before_cursor.len(), |(i, _)| i);
+    let prefix = &before_cursor[prefix_start..];
+
+    let rest = &before_cursor[..prefix_start];
+    let trimmed = rest.trim_end();
+    if trimmed.len() == rest.len() && !rest.is_empty() {
+        // Not separated by whitespace, so not a word boundary
+        return (None, prefix);
+    }
+    let previous_start = trimmed
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_word_char(c))
+        .last()
+        .map(|(i, _)| i);
+    let previous = previous_start.map(|i| &trimmed[i..]);
+    (previous, prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(word: &str) -> Vec<char> {
+        word.chars().collect()
+    }
+
+    fn distance_one(a: &str, b: &str) -> bool {
+        edit_distance_one(&chars(a), &chars(b))
+    }
+
+    fn model(data: &str) -> Model {
+        let mut model = Model::default();
+        model.parse(data);
+        model
+    }
+
+    #[test]
+    fn current_word_boundaries() {
+        assert_eq!(current_word(""), (None, ""));
+        assert_eq!(current_word("hel"), (None, "hel"));
+        assert_eq!(current_word("hello wor"), (Some("hello"), "wor"));
+        assert_eq!(current_word("hello  "), (Some("hello"), ""));
+        assert_eq!(current_word("don't st"), (Some("don't"), "st"));
+        assert_eq!(current_word("café na"), (Some("café"), "na"));
+    }
+
+    #[test]
+    fn current_word_stops_at_sentences_and_punctuation() {
+        assert_eq!(current_word("end. next"), (None, "next"));
+        assert_eq!(current_word("end, "), (None, ""));
+        // Joined by punctuation instead of whitespace
+        assert_eq!(current_word("foo-bar"), (None, "bar"));
+    }
+
+    #[test]
+    fn edit_distance_one_edits() {
+        assert!(distance_one("cat", "cut"));
+        assert!(distance_one("cat", "cats"));
+        assert!(distance_one("cats", "cat"));
+        assert!(distance_one("cat", "cast"));
+        assert!(distance_one("cat", "at"));
+    }
+
+    #[test]
+    fn edit_distance_one_transpositions() {
+        assert!(distance_one("teh", "the"));
+        assert!(distance_one("ab", "ba"));
+        assert!(distance_one("form", "from"));
+        assert!(!distance_one("abcd", "badc"));
+    }
+
+    #[test]
+    fn edit_distance_one_edge_cases() {
+        assert!(!distance_one("", ""));
+        assert!(distance_one("", "a"));
+        assert!(!distance_one("", "ab"));
+        assert!(!distance_one("cat", "cat"));
+        assert!(!distance_one("cat", "dog"));
+        assert!(!distance_one("cat", "catch"));
+    }
+
+    #[test]
+    fn suggest_ranks_by_count_then_alphabetically() {
+        let model = model("the 100\nthere 50\nthey 80\nthen 80\nthem 5\n");
+        assert_eq!(model.suggest(None, "the", 3), ["the", "then", "they"]);
+        assert_eq!(model.suggest(None, "the", 0), Vec::<String>::new());
+        assert_eq!(model.suggest(None, "x", 3), Vec::<String>::new());
+    }
+
+    #[test]
+    fn suggest_prefers_words_after_previous_word() {
+        let model = model("the 100\nthen 80\nthem 5\nof them 3\n");
+        assert_eq!(model.suggest(Some("of"), "the", 2), ["them", "the"]);
+        assert_eq!(model.suggest(Some("Of"), "", 3), ["them"]);
+        assert_eq!(model.suggest(None, "", 3), Vec::<String>::new());
+    }
+
+    #[test]
+    fn suggest_matches_case() {
+        let model = model("the 100\n");
+        assert_eq!(model.suggest(None, "Th", 1), ["The"]);
+        assert_eq!(model.suggest(None, "TH", 1), ["The"]);
+    }
+
+    #[test]
+    fn correction() {
+        let model = model("the 100\nthen 80\nthey 80\n");
+        assert_eq!(model.correction("teh").as_deref(), Some("the"));
+        assert_eq!(model.correction("Teh").as_deref(), Some("The"));
+        // Known and short words are left alone
+        assert_eq!(model.correction("then"), None);
+        assert_eq!(model.correction("te"), None);
+        assert_eq!(model.correction("xyzzy"), None);
+    }
+
+    #[test]
+    fn correction_ties_prefer_alphabetical() {
+        let model = model("then 80\nthey 80\n");
+        assert_eq!(model.correction("thex").as_deref(), Some("then"));
+    }
+}
@@ -0,0 +1,204 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use serde::{Deserialize, Serialize};
+
+use crate::layout::KeyPos;
+
+/// How the scanning highlight advances
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ScanMode {
+    /// Keys are pressed directly
+    #[default]
+    Off,
+    /// The highlight advances on a timer, one switch selects
+    Auto,
+    /// One switch advances the highlight, another selects
+    Step,
+}
+
+impl ScanMode {
+    pub const ALL: &'static [Self] = &[Self::Off, Self::Auto, Self::Step];
+}
+
+/// Which keys are highlighted together before picking a single key
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ScanMethod {
+    /// Rows, then keys in the selected row
+    #[default]
+    RowColumn,
+    /// Groups defined by the layout, then keys in the selected group
+    Group,
+}
+
+impl ScanMethod {
+    pub const ALL: &'static [Self] = &[Self::RowColumn, Self::Group];
+}
+
+/// Input from a switch
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScanInput {
+    /// Advance the highlight
+    Step,
+    /// Select the highlighted group or key
+    Select,
+}
+
+/// Position of the scanning highlight in a scan order of groups of keys
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Scanner {
+    /// Selected group, keys in it are scanned
+    group: Option<usize>,
+    /// Highlighted group, or key in the selected group
+    index: usize,
+}
+
+impl Scanner {
+    /// Go back to highlighting the first group
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Highlight the next group or key, going back to the groups after the
+    /// last key so a wrong group can be left
+    pub fn step(&mut self, order: &[Vec<KeyPos>]) {
+        match self.group.and_then(|group| order.get(group)) {
+            Some(keys) => {
+                self.index += 1;
+                if self.index >= keys.len() {
+                    self.reset();
+                }
+            }
+            None => {
+                self.group = None;
+                self.index = (self.index + 1) % order.len().max(1);
+            }
+        }
+    }
+
+    /// Select the highlighted group, or return the highlighted key
+    pub fn select(&mut self, order: &[Vec<KeyPos>]) -> Option<KeyPos> {
+        match self.group.and_then(|group| order.get(group)) {
+            Some(keys) => {
+                let key = keys.get(self.index).copied();
+                self.reset();
+                key
+            }
+            None => {
+                let keys = order.get(self.index)?;
+                if keys.len() == 1 {
+                    // Skip the key level for groups of one key
+                    self.reset();
+                    return keys.first().copied();
+                }
+                self.group = Some(self.index);
+                self.index = 0;
+                None
+            }
+        }
+    }
+
+    /// Returns true if the key is highlighted
+    pub fn is_highlighted(&self, order: &[Vec<KeyPos>], pos: KeyPos) -> bool {
+        match self.group.and_then(|group| order.get(group)) {
+            Some(keys) => keys.get(self.index) == Some(&pos),
+            None => order
+                .get(self.index)
+                .is_some_and(|keys| keys.contains(&pos)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order() -> Vec<Vec<KeyPos>> {
+        vec![
+            vec![(0, 0), (0, 1), (0, 2)],
+            vec![(1, 0)],
+            vec![(2, 0), (2, 1)],
+        ]
+    }
+
+    fn highlighted(scanner: &Scanner, order: &[Vec<KeyPos>]) -> Vec<KeyPos> {
+        order
+            .iter()
+            .flatten()
+            .copied()
+            .filter(|&pos| scanner.is_highlighted(order, pos))
+            .collect()
+    }
+
+    #[test]
+    fn groups_wrap_around() {
+        let order = order();
+        let mut scanner = Scanner::default();
+        assert_eq!(highlighted(&scanner, &order), order[0]);
+        scanner.step(&order);
+        assert_eq!(highlighted(&scanner, &order), order[1]);
+        scanner.step(&order);
+        assert_eq!(highlighted(&scanner, &order), order[2]);
+        scanner.step(&order);
+        assert_eq!(highlighted(&scanner, &order), order[0]);
+    }
+
+    #[test]
+    fn select_group_then_key() {
+        let order = order();
+        let mut scanner = Scanner::default();
+        scanner.step(&order);
+        scanner.step(&order);
+        assert_eq!(scanner.select(&order), None);
+        assert_eq!(highlighted(&scanner, &order), [(2, 0)]);
+        scanner.step(&order);
+        assert_eq!(highlighted(&scanner, &order), [(2, 1)]);
+        assert_eq!(scanner.select(&order), Some((2, 1)));
+        // Back to the first group after typing a key
+        assert_eq!(highlighted(&scanner, &order), order[0]);
+    }
+
+    #[test]
+    fn stepping_past_last_key_leaves_group() {
+        let order = order();
+        let mut scanner = Scanner::default();
+        assert_eq!(scanner.select(&order), None);
+        for _ in 0..3 {
+            scanner.step(&order);
+        }
+        assert_eq!(highlighted(&scanner, &order), order[0]);
+        assert_eq!(scanner.select(&order), None);
+        assert_eq!(highlighted(&scanner, &order), [(0, 0)]);
+    }
+
+    #[test]
+    fn single_key_group_selects_key() {
+        let order = order();
+        let mut scanner = Scanner::default();
+        scanner.step(&order);
+        assert_eq!(scanner.select(&order), Some((1, 0)));
+        assert_eq!(highlighted(&scanner, &order), order[0]);
+    }
+
+    #[test]
+    fn empty_order() {
+        let mut scanner = Scanner::default();
+        scanner.step(&[]);
+        assert_eq!(scanner.select(&[]), None);
+        assert!(!scanner.is_highlighted(&[], (0, 0)));
+        scanner.step(&[]);
+        assert_eq!(scanner.select(&[]), None);
+    }
+
+    #[test]
+    fn order_shrinking_under_selected_group() {
+        let order = order();
+        let mut scanner = Scanner::default();
+        scanner.step(&order);
+        scanner.step(&order);
+        assert_eq!(scanner.select(&order), None);
+        // The layer changed to one with fewer groups
+        let order = &order[..1];
+        scanner.step(order);
+        assert_eq!(highlighted(&scanner, order), order[0]);
+    }
+}
@@ -0,0 +1,376 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use cosmic::{Element, iced::Length, widget};
+use std::path::Path;
+
+use crate::{
+    Message,
+    config::{AppTheme, Config},
+    convert::Conversion,
+    fl,
+    layout::LayoutKind,
+    scan::{ScanMethod, ScanMode},
+};
+
+const APP_THEMES: [AppTheme; 3] = [AppTheme::System, AppTheme::Dark, AppTheme::Light];
+
+/// Smallest and largest key size in logical pixels
+pub const KEY_SIZES: std::ops::RangeInclusive<u16> = 32..=128;
+
+/// Shortest and longest automatic scanning interval in milliseconds
+pub const SCAN_INTERVALS: std::ops::RangeInclusive<u32> = 250..=5000;
+
+/// Shortest and longest dwell time in milliseconds
+pub const DWELL_TIMES: std::ops::RangeInclusive<u32> = 250..=3000;
+
+/// Labels for the dropdowns, created once because they are borrowed by the view
+pub struct Labels {
+    app_themes: Vec<String>,
+    layouts: Vec<String>,
+    scan_modes: Vec<String>,
+    scan_methods: Vec<String>,
+    conversions: Vec<String>,
+}
+
+impl Labels {
+    pub fn new() -> Self {
+        Self {
+            app_themes: vec![fl!("match-desktop"), fl!("dark"), fl!("light")],
+            layouts: LayoutKind::ALL
+                .iter()
+                .map(|kind| match kind {
+                    LayoutKind::Full => fl!("layout-full"),
+                    LayoutKind::Compact => fl!("layout-compact"),
+                    LayoutKind::Numpad => fl!("layout-numpad"),
+                })
+                .collect(),
+            scan_modes: ScanMode::ALL
+                .iter()
+                .map(|mode| match mode {
+                    ScanMode::Off => fl!("scan-off"),
+                    ScanMode::Auto => fl!("scan-auto"),
+                    ScanMode::Step => fl!("scan-step"),
+                })
+                .collect(),
+            scan_methods: ScanMethod::ALL
+                .iter()
+                .map(|method| match method {
+                    ScanMethod::RowColumn => fl!("scan-row-column"),
+                    ScanMethod::Group => fl!("scan-group"),
+                })
+                .collect(),
+            conversions: Conversion::ALL
+                .iter()
+                .map(|conversion| match conversion {
+                    Conversion::Off => fl!("conversion-off"),
+                    Conversion::Pinyin => fl!("conversion-pinyin"),
+                    Conversion::Kana => fl!("conversion-kana"),
+                    Conversion::Hangul => fl!("conversion-hangul"),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Message saving a changed copy of the config
+fn on_change<T>(
+    config: &Config,
+    apply: impl Fn(&mut Config, T) + Send + Sync + 'static,
+) -> impl Fn(T) -> Message + Send + Sync + 'static {
+    let config = config.clone();
+    move |value| {
+        let mut config = config.clone();
+        apply(&mut config, value);
+        Message::SetConfig(config)
+    }
+}
+
+fn toggle<'a>(
+    config: &Config,
+    title: String,
+    value: bool,
+    apply: impl Fn(&mut Config, bool) + Send + Sync + 'static,
+) -> Element<'a, Message> {
+    widget::settings::item(
+        title,
+        widget::toggler(value).on_toggle(on_change(config, apply)),
+    )
+    .into()
+}
+
+pub fn view<'a>(
+    config: &'a Config,
+    labels: &'a Labels,
+    languages: &'a [String],
+    missing_dictionary: Option<&Path>,
+) -> Element<'a, Message> {
+    let appearance = widget::settings::section()
+        .title(fl!("appearance"))
+        .add(widget::settings::item(
+            fl!("theme"),
+            widget::dropdown(
+                &labels.app_themes,
+                APP_THEMES
+                    .iter()
+                    .position(|theme| *theme == config.app_theme),
+                on_change(config, |config, index: usize| {
+                    config.app_theme = APP_THEMES[index];
+                }),
+            ),
+        ))
+        .add(widget::settings::item(
+            fl!("key-size"),
+            widget::row::with_capacity(2)
+                .push(
+                    widget::slider(
+                        KEY_SIZES,
+                        config.key_size,
+                        on_change(config, |config, key_size| config.key_size = key_size),
+                    )
+                    .width(Length::Fixed(160.0)),
+                )
+                .push(widget::text(config.key_size.to_string()))
+                .spacing(8),
+        ))
+        .add(toggle(
+            config,
+            fl!("key-preview"),
+            config.key_preview,
+            |config, value| config.key_preview = value,
+        ))
+        .add(
+            widget::settings::item::builder(fl!("high-contrast"))
+                .description(fl!("high-contrast-description"))
+                .toggler(
+                    config.high_contrast,
+                    on_change(config, |config, value| config.high_contrast = value),
+                ),
+        )
+        .add(
+            widget::settings::item::builder(fl!("key-icons"))
+                .description(fl!("key-icons-description"))
+                .toggler(
+                    config.key_icons,
+                    on_change(config, |config, value| config.key_icons = value),
+                ),
+        );
+
+    let layout = widget::settings::section()
+        .title(fl!("layout"))
+        .add(widget::settings::item(
+            fl!("layout"),
+            widget::dropdown(
+                &labels.layouts,
+                LayoutKind::ALL
+                    .iter()
+                    .position(|kind| *kind == config.layout),
+                on_change(config, |config, index: usize| {
+                    config.layout = LayoutKind::ALL[index];
+                }),
+            ),
+        ))
+        .add(toggle(
+            config,
+            fl!("numpad-auto"),
+            config.numpad_auto,
+            |config, value| config.numpad_auto = value,
+        ))
+        .add(toggle(
+            config,
+            fl!("auto-show"),
+            config.auto_show,
+            |config, value| config.auto_show = value,
+        ));
+
+    let switch_access = widget::settings::section()
+        .title(fl!("switch-access"))
+        .add(widget::settings::item(
+            fl!("scan-mode"),
+            widget::dropdown(
+                &labels.scan_modes,
+                ScanMode::ALL
+                    .iter()
+                    .position(|mode| *mode == config.scan_mode),
+                on_change(config, |config, index: usize| {
+                    config.scan_mode = ScanMode::ALL[index];
+                }),
+            ),
+        ))
+        .add(widget::settings::item(
+            fl!("scan-method"),
+            widget::dropdown(
+                &labels.scan_methods,
+                ScanMethod::ALL
+                    .iter()
+                    .position(|method| *method == config.scan_method),
+                on_change(config, |config, index: usize| {
+                    config.scan_method = ScanMethod::ALL[index];
+                }),
+            ),
+        ))
+        .add(widget::settings::item(
+            fl!("scan-interval"),
+            widget::row::with_capacity(2)
+                .push(
+                    widget::slider(
+                        SCAN_INTERVALS,
+                        config.scan_interval,
+                        on_change(config, |config, interval| config.scan_interval = interval),
+                    )
+                    .step(250u32)
+                    .width(Length::Fixed(160.0)),
+                )
+                .push(widget::text(format!("{} ms", config.scan_interval)))
+                .spacing(8),
+        ))
+        .add(
+            widget::settings::item::builder(fl!("scan-select-key"))
+                .description(fl!("scan-key-description"))
+                .control(
+                    widget::text_input("", &config.scan_select_key)
+                        .on_input(on_change(config, |config, key| {
+                            config.scan_select_key = key;
+                        }))
+                        .width(Length::Fixed(160.0)),
+                ),
+        )
+        .add(widget::settings::item(
+            fl!("scan-step-key"),
+            widget::text_input("", &config.scan_step_key)
+                .on_input(on_change(config, |config, key| config.scan_step_key = key))
+                .width(Length::Fixed(160.0)),
+        ))
+        .add(
+            widget::settings::item::builder(fl!("dwell"))
+                .description(fl!("dwell-description"))
+                .toggler(
+                    config.dwell,
+                    on_change(config, |config, value| config.dwell = value),
+                ),
+        )
+        .add(widget::settings::item(
+            fl!("dwell-time"),
+            widget::row::with_capacity(2)
+                .push(
+                    widget::slider(
+                        DWELL_TIMES,
+                        config.dwell_time,
+                        on_change(config, |config, time| config.dwell_time = time),
+                    )
+                    .step(50u32)
+                    .width(Length::Fixed(160.0)),
+                )
+                .push(widget::text(format!("{} ms", config.dwell_time)))
+                .spacing(8),
+        ))
+        .add(toggle(
+            config,
+            fl!("dwell-repeat"),
+            config.dwell_repeat,
+            |config, value| config.dwell_repeat = value,
+        ));
+
+    let typing = widget::settings::section()
+        .title(fl!("typing"))
+        .add(toggle(
+            config,
+            fl!("suggestions"),
+            config.suggestions,
+            |config, value| config.suggestions = value,
+        ))
+        .add(toggle(
+            config,
+            fl!("swipe-typing"),
+            config.swipe_typing,
+            |config, value| config.swipe_typing = value,
+        ))
+        .add(
+            widget::settings::item::builder(fl!("key-gestures"))
+                .description(fl!("key-gestures-description"))
+                .toggler(
+                    config.key_gestures,
+                    on_change(config, |config, value| config.key_gestures = value),
+                ),
+        )
+        .add(toggle(
+            config,
+            fl!("autocorrect"),
+            config.autocorrect,
+            |config, value| config.autocorrect = value,
+        ))
+        .add(toggle(
+            config,
+            fl!("auto-capitalize"),
+            config.auto_capitalize,
+            |config, value| config.auto_capitalize = value,
+        ))
+        .add(toggle(
+            config,
+            fl!("double-space-period"),
+            config.double_space_period,
+            |config, value| config.double_space_period = value,
+        ))
+        .add(
+            widget::settings::item::builder(fl!("conversion"))
+                .description(match missing_dictionary {
+                    Some(path) => fl!(
+                        "conversion-no-dictionary",
+                        path = path.display().to_string()
+                    ),
+                    None => fl!("conversion-description"),
+                })
+                .control(widget::dropdown(
+                    &labels.conversions,
+                    Conversion::ALL
+                        .iter()
+                        .position(|conversion| *conversion == config.conversion),
+                    on_change(config, |config, index: usize| {
+                        config.conversion = Conversion::ALL[index];
+                    }),
+                )),
+        )
+        .add(
+            widget::settings::item::builder(fl!("external-ime"))
+                .description(fl!("external-ime-description"))
+                .control(
+                    widget::toggler(config.external_ime)
+                        .on_toggle(on_change(config, |config, value| {
+                            config.external_ime = value
+                        })),
+                ),
+        );
+
+    let mut language_section = widget::settings::section().title(fl!("languages"));
+    if languages.is_empty() {
+        language_section = language_section.add(widget::text(fl!("no-languages")));
+    }
+    let selected = config.languages();
+    for language in languages.iter() {
+        let language_owned = language.clone();
+        language_section = language_section.add(toggle(
+            config,
+            language.clone(),
+            selected.contains(language),
+            move |config, value| {
+                let mut languages = config.languages();
+                languages.retain(|known| known != &language_owned);
+                if value {
+                    languages.push(language_owned.clone());
+                }
+                config.languages = languages;
+            },
+        ));
+    }
+
+    widget::scrollable(
+        widget::settings::view_column(vec![
+            appearance.into(),
+            layout.into(),
+            switch_access.into(),
+            typing.into(),
+            language_section.into(),
+        ])
+        .padding(16),
+    )
+    .into()
+}
@@ -0,0 +1,237 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::collections::HashMap;
+
+use crate::{layout::Layer, predict::Model};
+
+/// Position in key units from the top left of a layer
+pub type Point = (f32, f32);
+
+/// Number of points both paths are resampled to before comparing
+const SAMPLES: usize = 32;
+
+/// Paths further than this from a word's ideal path, in key units, are not matches
+const MAX_DISTANCE: f32 = 1.0;
+
+/// Centers of the single letter keys of a layer
+pub fn key_centers(layer: &Layer) -> HashMap<char, Point> {
+    let mut centers = HashMap::new();
+    for (y, row) in layer.rows.iter().enumerate() {
+        let mut x = 0.0;
+        for key in row.iter() {
+            let mut chars = key.name.chars();
+            if let (Some(c), None) = (chars.next(), chars.next())
+                && c.is_alphabetic()
+            {
+                let mut lower = c.to_lowercase();
+                if let (Some(lower), None) = (lower.next(), lower.next()) {
+                    centers.insert(lower, (x + key.width / 2.0, y as f32 + 0.5));
+                }
+            }
+            x += key.width;
+        }
+    }
+    centers
+}
+
+fn distance(a: Point, b: Point) -> f32 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+pub fn path_length(path: &[Point]) -> f32 {
+    path.windows(2).map(|w| distance(w[0], w[1])).sum()
+}
+
+/// Resample a path to points equally spaced along its length
+fn resample(path: &[Point]) -> Vec<Point> {
+    let length = path_length(path);
+    let Some(&first) = path.first() else {
+        return Vec::new();
+    };
+    if length == 0.0 {
+        return vec![first; SAMPLES];
+    }
+
+    let step = length / (SAMPLES - 1) as f32;
+    let mut points = Vec::with_capacity(SAMPLES);
+    points.push(first);
+    let mut travelled = 0.0;
+    let mut next = step;
+    for w in path.windows(2) {
+        let segment = distance(w[0], w[1]);
+        while segment > 0.0 && next <= travelled + segment && points.len() < SAMPLES - 1 {
+            let t = (next - travelled) / segment;
+            points.push((
+                w[0].0 + (w[1].0 - w[0].0) * t,
+                w[0].1 + (w[1].1 - w[0].1) * t,
+            ));
+            next += step;
+        }
+        travelled += segment;
+    }
+    points.push(path[path.len() - 1]);
+    points
+}
+
+/// Words whose key path best matches the swiped path, best first
+pub fn decode(
+    path: &[Point],
+    keys: &HashMap<char, Point>,
+    model: &Model,
+    limit: usize,
+) -> Vec<String> {
+    let (Some(&start), Some(&end)) = (path.first(), path.last()) else {
+        return Vec::new();
+    };
+    let path = resample(path);
+    let near = |point: Point, c: char| keys.get(&c).is_some_and(|&k| distance(k, point) < 1.0);
+
+    let mut candidates: Vec<(f32, &str)> = Vec::new();
+    'words: for (word, count) in model.words() {
+        let (Some(first), Some(last)) = (word.chars().next(), word.chars().last()) else {
+            continue;
+        };
+        if !near(start, first) || !near(end, last) {
+            continue;
+        }
+
+        let mut ideal = Vec::with_capacity(word.len());
+        for c in word.chars() {
+            let Some(&center) = keys.get(&c) else {
+                continue 'words;
+            };
+            if ideal.last() != Some(&center) {
+                ideal.push(center);
+            }
+        }
+        let ideal = resample(&ideal);
+
+        let mean = path
+            .iter()
+            .zip(ideal.iter())
+            .map(|(&a, &b)| distance(a, b))
+            .sum::<f32>()
+            / SAMPLES as f32;
+        if mean < MAX_DISTANCE {
+            // Prefer frequent words among similar shapes
+            candidates.push((mean - 0.02 * (count as f32 + 1.0).ln(), word));
+        }
+    }
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+    candidates
+        .into_iter()
+        .take(limit)
+        .map(|(_, word)| word.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{Action, Key};
+
+    fn key(name: &str, width: f32) -> Key {
+        Key {
+            name: name.to_string(),
+            icon: None,
+            width,
+            action: Action::None,
+        }
+    }
+
+    fn layer() -> Layer {
+        let row = |names: &str| names.chars().map(|c| key(&c.to_string(), 1.0)).collect();
+        Layer {
+            rows: vec![row("qwertyuiop"), row("asdfghjkl"), row("zxcvbnm")],
+            scan_groups: Vec::new(),
+        }
+    }
+
+    fn model(data: &str) -> Model {
+        let mut model = Model::default();
+        model.parse(data);
+        model
+    }
+
+    /// Path through the centers of the keys of a word, with points in between
+    fn path(keys: &HashMap<char, Point>, word: &str) -> Vec<Point> {
+        let centers: Vec<Point> = word.chars().map(|c| keys[&c]).collect();
+        let mut path = vec![centers[0]];
+        for w in centers.windows(2) {
+            for i in 1..=4 {
+                let t = i as f32 / 4.0;
+                path.push((
+                    w[0].0 + (w[1].0 - w[0].0) * t,
+                    w[0].1 + (w[1].1 - w[0].1) * t,
+                ));
+            }
+        }
+        path
+    }
+
+    #[test]
+    fn key_centers_of_letter_keys() {
+        let mut layer = layer();
+        layer.rows[2].insert(0, key("Shift", 1.5));
+        layer.rows[2].push(key("1", 1.0));
+        layer.rows[2].push(key("É", 1.0));
+        let keys = key_centers(&layer);
+        assert_eq!(keys[&'q'], (0.5, 0.5));
+        assert_eq!(keys[&'l'], (8.5, 1.5));
+        // Wide keys shift the keys after them
+        assert_eq!(keys[&'z'], (2.0, 2.5));
+        assert_eq!(keys[&'é'], (10.0, 2.5));
+        assert_eq!(keys.len(), 27);
+        assert!(!keys.contains_key(&'1'));
+    }
+
+    #[test]
+    fn resample_to_equal_spacing() {
+        assert!(resample(&[]).is_empty());
+        assert_eq!(resample(&[(1.0, 2.0)]), vec![(1.0, 2.0); SAMPLES]);
+        assert_eq!(
+            resample(&[(1.0, 2.0), (1.0, 2.0)]),
+            vec![(1.0, 2.0); SAMPLES]
+        );
+
+        let points = resample(&[(0.0, 0.0), (3.0, 0.0), (3.0, 3.1)]);
+        assert_eq!(points.len(), SAMPLES);
+        assert_eq!(points[0], (0.0, 0.0));
+        assert_eq!(points[SAMPLES - 1], (3.0, 3.1));
+        let step = 6.1 / (SAMPLES - 1) as f32;
+        for w in points.windows(2) {
+            // Points are equally spaced along the path, closer across the corner
+            let d = distance(w[0], w[1]);
+            assert!(d <= step + 1e-4 && d > step * 0.7, "{d} {step}");
+        }
+    }
+
+    #[test]
+    fn decode_matches_word_shapes() {
+        let keys = key_centers(&layer());
+        let model = model("the 100\ntie 10\ntoe 10\nhello 50\nhi 20\n");
+        assert_eq!(decode(&path(&keys, "the"), &keys, &model, 1), ["the"]);
+        assert_eq!(decode(&path(&keys, "hello"), &keys, &model, 1), ["hello"]);
+        assert_eq!(decode(&path(&keys, "hi"), &keys, &model, 3), ["hi"]);
+        // Words must start and end near the ends of the path
+        assert!(decode(&path(&keys, "je"), &keys, &model, 3).is_empty());
+        assert!(decode(&[], &keys, &model, 3).is_empty());
+    }
+
+    #[test]
+    fn decode_prefers_frequent_words_of_the_same_shape() {
+        let keys = key_centers(&layer());
+        // Repeated letters do not change the path
+        let model = model("to 100\ntoo 300\n");
+        assert_eq!(decode(&path(&keys, "to"), &keys, &model, 2), ["too", "to"]);
+        assert_eq!(decode(&path(&keys, "to"), &keys, &model, 1), ["too"]);
+        assert!(decode(&path(&keys, "to"), &keys, &model, 0).is_empty());
+    }
+
+    #[test]
+    fn decode_skips_words_with_missing_keys() {
+        let keys = key_centers(&layer());
+        let model = model("tie 10\nt1e 1000\n");
+        assert_eq!(decode(&path(&keys, "tie"), &keys, &model, 3), ["tie"]);
+    }
+}
@@ -2,17 +2,21 @@
 
 use calloop::{EventLoop, channel};
 use calloop_wayland_source::WaylandSource;
-use std::{collections::HashMap, os::fd::AsFd, thread, time};
+use std::{collections::HashMap, fmt, os::fd::AsFd, thread, time};
 use wayland_client::{
     Connection, Dispatch, Proxy, QueueHandle, WEnum, delegate_noop,
     protocol::{
-        wl_keyboard::WlKeyboard,
+        wl_callback::{self, WlCallback},
+        wl_display::WlDisplay,
+        wl_keyboard::{self, WlKeyboard},
         wl_registry,
         wl_seat::{self, WlSeat},
     },
 };
+use wayland_protocols::wp::text_input::zv3::client::zwp_text_input_v3;
 use wayland_protocols_misc::{
     zwp_input_method_v2::client::{
+        zwp_input_method_keyboard_grab_v2::{self, ZwpInputMethodKeyboardGrabV2},
         zwp_input_method_manager_v2::ZwpInputMethodManagerV2,
         zwp_input_method_v2::{self, ZwpInputMethodV2},
     },
@@ -23,21 +27,154 @@ use wayland_protocols_misc::{
 };
 use xkbcommon::xkb;
 
-use crate::{Message, layout::Layout};
+use crate::{Message, layout::LayoutKind, scan::ScanInput};
 
-pub use xkb::Keycode;
+pub use wayland_client::protocol::wl_output::WlOutput;
+pub use xkb::{Keycode, Keysym};
+pub use zwp_text_input_v3::{ChangeCause, ContentHint, ContentPurpose};
 
-#[derive(Clone, Copy, Debug)]
+/// Typed keys and text are never logged, so `Debug` only shows the kind of event
+#[derive(Clone)]
 pub enum VkEvent {
     Key(Keycode, bool),
+    /// Commit text to the focused text field through the input method
+    CommitString(String),
+    /// Replace the given number of bytes before the cursor with text
+    ReplaceText {
+        before_length: u32,
+        text: String,
+    },
+    /// Show text being composed at the cursor, cleared by committing text
+    Preedit(String),
+    /// Keys used as scanning switches, grabbed from the physical keyboard
+    Switches(Vec<(Keysym, ScanInput)>),
+    /// Take the input method of seats, or leave it to another input method
+    InputMethod(bool),
+}
+
+impl fmt::Debug for VkEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key(_, pressed) => f.debug_tuple("Key").field(pressed).finish_non_exhaustive(),
+            Self::CommitString(_) => f.debug_tuple("CommitString").finish_non_exhaustive(),
+            Self::ReplaceText { .. } => f.debug_struct("ReplaceText").finish_non_exhaustive(),
+            Self::Preedit(_) => f.debug_tuple("Preedit").finish_non_exhaustive(),
+            Self::Switches(switches) => f.debug_tuple("Switches").field(switches).finish(),
+            Self::InputMethod(input_method) => {
+                f.debug_tuple("InputMethod").field(input_method).finish()
+            }
+        }
+    }
+}
+
+/// Text of the focused field is never logged, so `Debug` omits it
+#[derive(Clone, Default, Eq, PartialEq)]
+pub struct SurroundingText {
+    pub text: String,
+    /// Byte offset of the cursor in text
+    pub cursor: u32,
+    /// Byte offset of the selection anchor in text
+    pub anchor: u32,
+}
+
+impl fmt::Debug for SurroundingText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SurroundingText")
+            .field("len", &self.text.len())
+            .field("cursor", &self.cursor)
+            .field("anchor", &self.anchor)
+            .finish()
+    }
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+impl SurroundingText {
+    pub fn before_cursor(&self) -> &str {
+        self.text.get(..self.cursor as usize).unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct VkState {
     pub active: bool,
+    pub hint: ContentHint,
+    pub purpose: ContentPurpose,
+    pub num_lock: bool,
+    pub surrounding_text: Option<SurroundingText>,
+    pub change_cause: ChangeCause,
+}
+
+impl Default for VkState {
+    fn default() -> Self {
+        Self {
+            active: false,
+            hint: ContentHint::None,
+            purpose: ContentPurpose::Normal,
+            num_lock: false,
+            surrounding_text: None,
+            change_cause: ChangeCause::InputMethod,
+        }
+    }
+}
+
+impl VkState {
+    /// Returns true if the focused text field only accepts numbers
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self.purpose,
+            ContentPurpose::Digits
+                | ContentPurpose::Number
+                | ContentPurpose::Phone
+                | ContentPurpose::Pin
+        )
+    }
+
+    /// Returns true if text typed in the focused text field must not be remembered
+    pub fn is_sensitive(&self) -> bool {
+        self.hint.contains(ContentHint::SensitiveData)
+            || matches!(self.purpose, ContentPurpose::Password | ContentPurpose::Pin)
+    }
+
+    /// Returns true if the focused text field holds prose that may be corrected
+    pub fn is_free_text(&self) -> bool {
+        !self.is_sensitive()
+            && !self.is_numeric()
+            && !matches!(
+                self.purpose,
+                ContentPurpose::Url | ContentPurpose::Email | ContentPurpose::Terminal
+            )
+    }
+}
+
+/// Print every key of the first layout with its keysyms, and all modifiers
+pub fn dump_keymap(keymap: &xkb::Keymap) {
+    // Only show first layout for now
+    if keymap.num_layouts() > 0 {
+        let layout = 0;
+        println!("layout {}: {}", layout, keymap.layout_get_name(layout));
+        for kc_raw in keymap.min_keycode().raw()..=keymap.max_keycode().raw() {
+            let kc = xkb::Keycode::new(kc_raw);
+            print!("  keycode {:?} {:?}:", kc, keymap.key_get_name(kc));
+            for level in 0..keymap.num_levels_for_key(kc, layout) {
+                for ks in keymap.key_get_syms_by_level(kc, layout, level) {
+                    print!(" {:?} ({:?})", ks, xkb::keysym_get_name(*ks));
+                }
+            }
+            println!();
+        }
+    }
+
+    for modifier in keymap.mods() {
+        println!("mod {}", modifier);
+    }
 }
 
-pub fn vk_channels() -> (channel::Sender<VkEvent>, channel::Channel<Message>) {
+/// Start the wayland thread, using only the seat with the given name if set,
+/// and taking the input method of the seat if `input_method` is true
+pub fn vk_channels(
+    dump_keymap: bool,
+    seat_name: Option<String>,
+    input_method: bool,
+) -> (channel::Sender<VkEvent>, channel::Channel<Message>) {
     let (vke_tx, vke_rx) = channel::channel();
     let (msg_tx, msg_rx) = channel::channel();
 
@@ -46,16 +183,51 @@ pub fn vk_channels() -> (channel::Sender<VkEvent>, channel::Channel<Message>) {
         let mut event_loop: EventLoop<State> = EventLoop::try_new().unwrap();
         let loop_handle = event_loop.handle();
 
+        let conn = Connection::connect_to_env().unwrap();
+
+        let event_queue = conn.new_event_queue();
+        let qh = event_queue.handle();
+
         let timer = time::Instant::now();
+        let grab_qh = qh.clone();
         loop_handle
             .insert_source(vke_rx, move |event, _metadata, state| {
-                eprintln!("{:?}", event);
                 let channel::Event::Msg(vke) = event else {
                     return;
                 };
+                if let VkEvent::Switches(switches) = vke {
+                    state.switches = switches;
+                    let grab = !state.switches.is_empty();
+                    for (&seat_id, seat) in state.seats.iter_mut() {
+                        seat.update_grab(grab, &grab_qh, seat_id);
+                    }
+                    return;
+                }
+                if let VkEvent::InputMethod(input_method) = vke {
+                    state.input_method = input_method;
+                    // Seats without a virtual keyboard take it once their keymap is known
+                    for (&seat_id, seat) in state.seats.iter_mut() {
+                        if seat.vk.is_none() {
+                            continue;
+                        }
+                        if input_method {
+                            if let Some(imm) = &state.imm {
+                                seat.take_input_method(imm, &state.display, &grab_qh, seat_id);
+                            }
+                        } else if seat.leave_input_method() {
+                            state
+                                .msg_tx
+                                .send(Message::VkState(seat_id, seat.vk_state.clone()))
+                                .unwrap();
+                        }
+                    }
+                    return;
+                }
                 //TODO: retry keys once seat and vk are available?
                 //TODO: which seat should be used?
-                for (_id, seat) in state.seats.iter_mut() {
+                // Text is committed through the input method, which another one may hold
+                let mut without_im = false;
+                for (&seat_id, seat) in state.seats.iter_mut() {
                     let Some(vk) = &seat.vk else {
                         continue;
                     };
@@ -64,8 +236,9 @@ pub fn vk_channels() -> (channel::Sender<VkEvent>, channel::Channel<Message>) {
                     };
                     //TODO: What happens on time rollover?
                     let time = timer.elapsed().as_millis() as u32;
-                    match vke {
-                        VkEvent::Key(kc, pressed) => {
+                    match &vke {
+                        &VkEvent::Key(kc, pressed) => {
+                            let keysym = xkb.key_get_one_sym(kc);
                             let comps = xkb.update_key(
                                 kc,
                                 if pressed {
@@ -77,7 +250,6 @@ pub fn vk_channels() -> (channel::Sender<VkEvent>, channel::Channel<Message>) {
                             //TODO: check comps bits
                             if comps & xkb::STATE_MODS_EFFECTIVE > 0 {
                                 let mods = xkb.serialize_mods(comps);
-                                println!("{:#x}: {:#x}", comps, mods);
                                 vk.modifiers(mods, 0, 0, 0);
 
                                 //TODO: is it sane to do per-key check of level?
@@ -88,19 +260,62 @@ pub fn vk_channels() -> (channel::Sender<VkEvent>, channel::Channel<Message>) {
                                 u32::from(kc.raw().checked_sub(8).unwrap()),
                                 if pressed { 1 } else { 0 },
                             );
+
+                            if pressed {
+                                state.msg_tx.send(Message::Typed(seat_id, keysym)).unwrap();
+                            }
+
+                            let num_lock = xkb.led_name_is_active(xkb::LED_NAME_NUM);
+                            if num_lock != seat.vk_state.num_lock {
+                                seat.vk_state.num_lock = num_lock;
+                                state
+                                    .msg_tx
+                                    .send(Message::VkState(seat_id, seat.vk_state.clone()))
+                                    .unwrap();
+                            }
+                        }
+                        VkEvent::CommitString(text) => {
+                            let Some(im) = &seat.im else {
+                                without_im = true;
+                                continue;
+                            };
+                            im.commit_string(text.clone());
+                            im.commit(seat.serial);
+                        }
+                        VkEvent::ReplaceText {
+                            before_length,
+                            text,
+                        } => {
+                            let Some(im) = &seat.im else {
+                                without_im = true;
+                                continue;
+                            };
+                            im.delete_surrounding_text(*before_length, 0);
+                            im.commit_string(text.clone());
+                            im.commit(seat.serial);
                         }
+                        VkEvent::Preedit(text) => {
+                            let Some(im) = &seat.im else {
+                                without_im = true;
+                                continue;
+                            };
+                            // Cursor at the end of the composed text
+                            let cursor = text.len() as i32;
+                            im.set_preedit_string(text.clone(), cursor, cursor);
+                            im.commit(seat.serial);
+                        }
+                        VkEvent::Switches(_) | VkEvent::InputMethod(_) => {}
                     }
                     return;
                 }
-                eprintln!("no seat with virtual keyboard found");
+                if without_im {
+                    log::warn!(target: "vk", "no seat holds the input method to commit text");
+                } else {
+                    log::warn!(target: "vk", "no seat with virtual keyboard found");
+                }
             })
             .unwrap();
 
-        let conn = Connection::connect_to_env().unwrap();
-
-        let event_queue = conn.new_event_queue();
-        let qh = event_queue.handle();
-
         let display = conn.display();
         display.get_registry(&qh, ());
 
@@ -110,10 +325,15 @@ pub fn vk_channels() -> (channel::Sender<VkEvent>, channel::Channel<Message>) {
 
         let mut state = State {
             msg_tx,
+            display,
             seats: HashMap::new(),
             imm: None,
             vkm: None,
             xkb_ctx: xkb::Context::new(0),
+            dump_keymap,
+            seat_name,
+            input_method,
+            switches: Vec::new(),
         };
         while let Ok(_) = event_loop.dispatch(None, &mut state) {}
     });
@@ -123,19 +343,104 @@ pub fn vk_channels() -> (channel::Sender<VkEvent>, channel::Channel<Message>) {
 
 struct Seat {
     wl: WlSeat,
+    name: Option<String>,
     keyboard: Option<WlKeyboard>,
     state: Option<xkb::State>,
     im: Option<ZwpInputMethodV2>,
     vk: Option<ZwpVirtualKeyboardV1>,
     vk_state: VkState,
+    /// Number of input method done events, used to commit input method state
+    serial: u32,
+    /// Physical keyboard grab, forwarded through the virtual keyboard except for switches
+    grab: Option<ZwpInputMethodKeyboardGrabV2>,
+    grab_state: Option<xkb::State>,
+}
+
+impl Seat {
+    /// Grab the physical keyboard while a text field is focused if switches are used
+    fn update_grab(&mut self, grab: bool, qh: &QueueHandle<State>, seat_id: u32) {
+        let grab = grab && self.vk_state.active;
+        if grab && self.grab.is_none() {
+            if let Some(im) = &self.im {
+                log::debug!(target: "grab", "seat {seat_id} grabbing keyboard");
+                self.grab = Some(im.grab_keyboard(qh, seat_id));
+            }
+        } else if !grab && let Some(keyboard_grab) = self.grab.take() {
+            log::debug!(target: "grab", "seat {seat_id} releasing keyboard");
+            keyboard_grab.release();
+            self.grab_state = None;
+        }
+    }
+
+    /// Follow Num Lock of a physical keyboard, returning true if it changed
+    fn follow_num_lock(&mut self, num_lock: bool) -> bool {
+        if let Some(xkb) = &mut self.state
+            && xkb.led_name_is_active(xkb::LED_NAME_NUM) != num_lock
+        {
+            // Keys typed on the keyboard use the same Num Lock state
+            let index = xkb.get_keymap().mod_get_index(xkb::MOD_NAME_NUM);
+            if index != xkb::MOD_INVALID {
+                xkb.update_mask(
+                    xkb.serialize_mods(xkb::STATE_MODS_DEPRESSED),
+                    xkb.serialize_mods(xkb::STATE_MODS_LATCHED),
+                    xkb.serialize_mods(xkb::STATE_MODS_LOCKED) ^ (1 << index),
+                    0,
+                    0,
+                    xkb.serialize_layout(xkb::STATE_LAYOUT_LOCKED),
+                );
+            }
+        }
+        if self.vk_state.num_lock == num_lock {
+            return false;
+        }
+        self.vk_state.num_lock = num_lock;
+        true
+    }
+
+    /// Take the input method of the seat, unless it is held already
+    fn take_input_method(
+        &mut self,
+        imm: &ZwpInputMethodManagerV2,
+        display: &WlDisplay,
+        qh: &QueueHandle<State>,
+        seat_id: u32,
+    ) {
+        if self.im.is_none() {
+            log::debug!(target: "im", "seat {seat_id} taking input method");
+            self.im = Some(imm.get_input_method(&self.wl, qh, seat_id));
+            // Held by another input method if unavailable before the sync is done
+            display.sync(qh, seat_id);
+        }
+    }
+
+    /// Leave the input method to another input method, returning true if it was held
+    fn leave_input_method(&mut self) -> bool {
+        if let Some(keyboard_grab) = self.grab.take() {
+            keyboard_grab.release();
+            self.grab_state = None;
+        }
+        let Some(im) = self.im.take() else {
+            return false;
+        };
+        im.destroy();
+        self.vk_state.active = false;
+        true
+    }
 }
 
 struct State {
     msg_tx: channel::Sender<Message>,
+    display: WlDisplay,
     seats: HashMap<u32, Seat>,
     imm: Option<ZwpInputMethodManagerV2>,
     vkm: Option<ZwpVirtualKeyboardManagerV1>,
     xkb_ctx: xkb::Context,
+    /// Print keymaps as they are received
+    dump_keymap: bool,
+    seat_name: Option<String>,
+    /// Take the input method of seats, false if another input method is used
+    input_method: bool,
+    switches: Vec<(Keysym, ScanInput)>,
 }
 
 impl Dispatch<wl_registry::WlRegistry, ()> for State {
@@ -154,24 +459,28 @@ impl Dispatch<wl_registry::WlRegistry, ()> for State {
         } = event
         {
             if interface == WlSeat::interface().name {
-                eprintln!("Seat");
+                log::debug!(target: "registry", "seat {name} version {version}");
                 state.seats.insert(
                     name,
                     Seat {
                         wl: registry.bind(name, version, qh, name),
+                        name: None,
                         keyboard: None,
                         state: None,
                         im: None,
                         vk: None,
                         vk_state: Default::default(),
+                        serial: 0,
+                        grab: None,
+                        grab_state: None,
                     },
                 );
             } else if interface == ZwpInputMethodManagerV2::interface().name {
-                eprintln!("Input Method Interface");
+                log::debug!(target: "registry", "input method manager version {version}");
                 assert!(state.imm.is_none());
                 state.imm = Some(registry.bind(name, version, qh, ()));
             } else if interface == ZwpVirtualKeyboardManagerV1::interface().name {
-                eprintln!("Virtual Keyboard Interface");
+                log::debug!(target: "registry", "virtual keyboard manager version {version}");
                 assert!(state.vkm.is_none());
                 state.vkm = Some(registry.bind(name, version, qh, ()));
             }
@@ -190,29 +499,55 @@ impl Dispatch<WlKeyboard, u32> for State {
     ) {
         use wayland_client::protocol::wl_keyboard::Event;
 
-        eprintln!("Keyboard event {seat_id}: {event:?}");
-        //TODO: why is this event called on every keypress?
-        if let Event::Keymap { format, fd, size } = event {
-            let Some(ref imm) = state.imm else {
-                eprintln!("no input method manager found");
+        // Pressed keys and modifiers are keystrokes, never log them
+        if !matches!(
+            event,
+            Event::Enter { .. } | Event::Key { .. } | Event::Modifiers { .. }
+        ) {
+            log::trace!(target: "keyboard", "seat {seat_id} event {event:?}");
+        }
+        if let Event::Modifiers { mods_locked, .. } = event {
+            let Some(seat) = state.seats.get_mut(&seat_id) else {
                 return;
             };
+            // The physical keyboard uses the keymap of the virtual keyboard
+            let num_lock = seat.state.as_ref().is_some_and(|xkb| {
+                let index = xkb.get_keymap().mod_get_index(xkb::MOD_NAME_NUM);
+                index != xkb::MOD_INVALID && mods_locked & (1 << index) != 0
+            });
+            if seat.follow_num_lock(num_lock) {
+                state
+                    .msg_tx
+                    .send(Message::VkState(seat_id, seat.vk_state.clone()))
+                    .unwrap();
+            }
+            return;
+        }
+        //TODO: why is this event called on every keypress?
+        if let Event::Keymap { format, fd, size } = event {
             let Some(ref vkm) = state.vkm else {
-                eprintln!("no virtual keyboard manager found");
+                log::error!(target: "keyboard", "no virtual keyboard manager found");
                 return;
             };
             let Some(seat) = state.seats.get_mut(&seat_id) else {
-                eprintln!("seat {seat_id} not found");
+                log::warn!(target: "keyboard", "seat {seat_id} not found");
                 return;
             };
+            if state.seat_name.is_some() && seat.name != state.seat_name {
+                log::debug!(target: "keyboard", "ignoring seat {seat_id} {:?}", seat.name);
+                return;
+            }
             if seat.vk.is_some() {
                 //TODO: allow resetting if the physical keyboard's layout was reset
-                eprintln!("refusing to reset virtual keyboard keymap");
+                log::debug!(target: "keyboard", "refusing to reset virtual keyboard keymap");
                 return;
             }
-            let im = seat
-                .im
-                .get_or_insert_with(|| imm.get_input_method(&seat.wl, qh, seat_id));
+            if state.input_method {
+                match &state.imm {
+                    Some(imm) => seat.take_input_method(imm, &state.display, qh, seat_id),
+                    None => log::error!(target: "keyboard", "no input method manager found"),
+                }
+            }
             let vk = seat
                 .vk
                 .get_or_insert_with(|| vkm.create_virtual_keyboard(&seat.wl, qh, ()));
@@ -227,37 +562,31 @@ impl Dispatch<WlKeyboard, u32> for State {
                 )
             } {
                 Ok(Some(keymap)) => {
-                    for layout in 0..keymap.num_layouts() {
-                        println!("layout {}: {}", layout, keymap.layout_get_name(layout));
-                        for kc_raw in keymap.min_keycode().raw()..=keymap.max_keycode().raw() {
-                            let kc = xkb::Keycode::new(kc_raw);
-                            print!("  keycode {:?} {:?}:", kc, keymap.key_get_name(kc));
-                            for level in 0..keymap.num_levels_for_key(kc, layout) {
-                                for ks in keymap.key_get_syms_by_level(kc, layout, level) {
-                                    print!(" {:?} ({:?})", ks, xkb::keysym_get_name(*ks));
-                                }
-                            }
-                            println!();
-                        }
-                        // Only show first layout for now
-                        break;
-                    }
-
-                    for modifier in keymap.mods() {
-                        println!("mod {}", modifier);
+                    log::info!(
+                        target: "keyboard",
+                        "seat {seat_id} keymap with {} layouts",
+                        keymap.num_layouts()
+                    );
+                    if state.dump_keymap {
+                        dump_keymap(&keymap);
                     }
 
                     seat.state = Some(xkb::State::new(&keymap));
                     state
                         .msg_tx
-                        .send(Message::Layout(Layout::from(&keymap)))
+                        .send(Message::Layouts(
+                            LayoutKind::ALL
+                                .iter()
+                                .map(|kind| kind.layout(&keymap))
+                                .collect(),
+                        ))
                         .unwrap();
                 }
                 Ok(None) => {
-                    eprintln!("no keymap found");
+                    log::warn!(target: "keyboard", "no keymap found");
                 }
                 Err(err) => {
-                    eprintln!("failed to parse keymap: {}", err);
+                    log::error!(target: "keyboard", "failed to parse keymap: {}", err);
                 }
             }
         }
@@ -274,23 +603,28 @@ impl Dispatch<WlSeat, u32> for State {
         qh: &QueueHandle<Self>,
     ) {
         use wl_seat::Event;
-        eprintln!("Seat {seat_id} event: {event:?}");
+        log::debug!(target: "seat", "seat {seat_id} event {event:?}");
         match event {
             Event::Capabilities { capabilities } => {
                 let WEnum::Value(caps) = capabilities else {
-                    eprintln!("invalid seat {seat_id} capabilities {capabilities:?}");
+                    log::warn!(target: "seat", "invalid seat {seat_id} capabilities {capabilities:?}");
                     return;
                 };
                 if caps.contains(wl_seat::Capability::Keyboard) {
-                    eprintln!("Seat {seat_id} keyboard");
+                    log::debug!(target: "seat", "seat {seat_id} has keyboard");
                     let Some(seat) = state.seats.get_mut(&seat_id) else {
-                        eprintln!("failed to find seat {seat_id}");
+                        log::warn!(target: "seat", "failed to find seat {seat_id}");
                         return;
                     };
                     assert!(seat.keyboard.is_none());
                     seat.keyboard = Some(wl_seat.get_keyboard(qh, seat_id));
                 }
             }
+            Event::Name { name } => {
+                if let Some(seat) = state.seats.get_mut(&seat_id) {
+                    seat.name = Some(name);
+                }
+            }
             _ => {}
         }
     }
@@ -306,23 +640,63 @@ impl Dispatch<ZwpInputMethodV2, u32> for State {
         qh: &QueueHandle<Self>,
     ) {
         use zwp_input_method_v2::Event;
-        eprintln!("Input method {seat_id}: {:?}", event);
+        // Surrounding text may hold anything typed, never log it
+        if !matches!(event, Event::SurroundingText { .. }) {
+            log::debug!(target: "im", "seat {seat_id} event {:?}", event);
+        }
+        let grab = !state.switches.is_empty();
         let Some(seat) = state.seats.get_mut(&seat_id) else {
-            eprintln!("seat {seat_id} not found");
+            log::warn!(target: "im", "seat {seat_id} not found");
             return;
         };
         match event {
             Event::Activate => {
                 seat.vk_state.active = true;
+                // Content type is reset on activation
+                seat.vk_state.hint = ContentHint::None;
+                seat.vk_state.purpose = ContentPurpose::Normal;
+                seat.vk_state.surrounding_text = None;
+                seat.vk_state.change_cause = ChangeCause::InputMethod;
+                seat.update_grab(grab, qh, seat_id);
             }
             Event::Deactivate => {
                 seat.vk_state.active = false;
+                seat.update_grab(grab, qh, seat_id);
+            }
+            Event::SurroundingText {
+                text,
+                cursor,
+                anchor,
+            } => {
+                seat.vk_state.surrounding_text = Some(SurroundingText {
+                    text,
+                    cursor,
+                    anchor,
+                });
+            }
+            Event::TextChangeCause { cause } => {
+                seat.vk_state.change_cause = cause.into_result().unwrap_or(ChangeCause::Other);
+            }
+            Event::ContentType { hint, purpose } => {
+                seat.vk_state.hint = hint.into_result().unwrap_or(ContentHint::None);
+                seat.vk_state.purpose = purpose.into_result().unwrap_or(ContentPurpose::Normal);
             }
             Event::Done => {
+                seat.serial = seat.serial.wrapping_add(1);
+                state
+                    .msg_tx
+                    .send(Message::VkState(seat_id, seat.vk_state.clone()))
+                    .unwrap();
+            }
+            Event::Unavailable => {
+                // Logged by the app once, this is sent again on every retry
+                log::debug!(target: "im", "seat {seat_id} input method is used by another input method");
+                seat.leave_input_method();
                 state
                     .msg_tx
-                    .send(Message::VkState(seat_id, seat.vk_state))
+                    .send(Message::VkState(seat_id, seat.vk_state.clone()))
                     .unwrap();
+                state.msg_tx.send(Message::ImUnavailable(seat_id)).unwrap();
             }
             //TODO: handle more events
             _ => {}
@@ -330,6 +704,154 @@ impl Dispatch<ZwpInputMethodV2, u32> for State {
     }
 }
 
+impl Dispatch<ZwpInputMethodKeyboardGrabV2, u32> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwpInputMethodKeyboardGrabV2,
+        event: zwp_input_method_keyboard_grab_v2::Event,
+        &seat_id: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use zwp_input_method_keyboard_grab_v2::Event;
+        // Grabbed keys and modifiers are keystrokes, never log them
+        if !matches!(event, Event::Key { .. } | Event::Modifiers { .. }) {
+            log::debug!(target: "grab", "seat {seat_id} event {event:?}");
+        }
+        let Some(seat) = state.seats.get_mut(&seat_id) else {
+            log::warn!(target: "grab", "seat {seat_id} not found");
+            return;
+        };
+        match event {
+            Event::Keymap { format, fd, size } => {
+                let vk_fd = fd.try_clone();
+                match unsafe {
+                    xkb::Keymap::new_from_fd(
+                        &state.xkb_ctx,
+                        fd,
+                        size.try_into().unwrap(),
+                        format.into(),
+                        0,
+                    )
+                } {
+                    Ok(Some(keymap)) => {
+                        // Grabbed keys are forwarded as keycodes, which need the same keymap
+                        let matches = seat.state.as_ref().is_some_and(|xkb| {
+                            xkb.get_keymap().get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1)
+                                == keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1)
+                        });
+                        if !matches && let Some(vk) = &seat.vk {
+                            match vk_fd {
+                                Ok(vk_fd) => {
+                                    log::info!(target: "grab", "seat {seat_id} using keymap of grab");
+                                    vk.keymap(format.into(), vk_fd.as_fd(), size);
+                                    seat.state = Some(xkb::State::new(&keymap));
+                                    state
+                                        .msg_tx
+                                        .send(Message::Layouts(
+                                            LayoutKind::ALL
+                                                .iter()
+                                                .map(|kind| kind.layout(&keymap))
+                                                .collect(),
+                                        ))
+                                        .unwrap();
+                                }
+                                Err(err) => {
+                                    // Keys would be typed with the wrong keymap
+                                    log::error!(target: "grab", "failed to copy keymap, releasing grab: {}", err);
+                                    if let Some(keyboard_grab) = seat.grab.take() {
+                                        keyboard_grab.release();
+                                    }
+                                    return;
+                                }
+                            }
+                        }
+                        seat.grab_state = Some(xkb::State::new(&keymap));
+                    }
+                    Ok(None) => {
+                        log::warn!(target: "grab", "no keymap found");
+                    }
+                    Err(err) => {
+                        log::error!(target: "grab", "failed to parse keymap: {}", err);
+                    }
+                }
+            }
+            Event::Key {
+                time,
+                key,
+                state: key_state,
+                ..
+            } => {
+                let switch = seat.grab_state.as_ref().and_then(|xkb| {
+                    let keysym = xkb.key_get_one_sym(Keycode::new(key + 8));
+                    state
+                        .switches
+                        .iter()
+                        .find(|(switch, _)| *switch == keysym)
+                        .map(|&(_, input)| input)
+                });
+                match switch {
+                    Some(input) => {
+                        // Switches act on press and are not typed
+                        if key_state == WEnum::Value(wl_keyboard::KeyState::Pressed) {
+                            state.msg_tx.send(Message::Scan(input)).unwrap();
+                        }
+                    }
+                    None => {
+                        if let Some(vk) = &seat.vk {
+                            vk.key(time, key, key_state.into());
+                        }
+                    }
+                }
+            }
+            Event::Modifiers {
+                mods_depressed,
+                mods_latched,
+                mods_locked,
+                group,
+                ..
+            } => {
+                let num_lock = seat.grab_state.as_mut().map(|xkb| {
+                    xkb.update_mask(mods_depressed, mods_latched, mods_locked, 0, 0, group);
+                    xkb.led_name_is_active(xkb::LED_NAME_NUM)
+                });
+                if let Some(num_lock) = num_lock
+                    && seat.follow_num_lock(num_lock)
+                {
+                    state
+                        .msg_tx
+                        .send(Message::VkState(seat_id, seat.vk_state.clone()))
+                        .unwrap();
+                }
+                if let Some(vk) = &seat.vk {
+                    vk.modifiers(mods_depressed, mods_latched, mods_locked, group);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlCallback, u32> for State {
+    fn event(
+        state: &mut Self,
+        _: &WlCallback,
+        event: wl_callback::Event,
+        &seat_id: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_callback::Event::Done { .. } = event
+            && state
+                .seats
+                .get(&seat_id)
+                .is_some_and(|seat| seat.im.is_some())
+        {
+            state.msg_tx.send(Message::ImAvailable(seat_id)).unwrap();
+        }
+    }
+}
+
 delegate_noop!(State: ZwpInputMethodManagerV2);
 delegate_noop!(State: ZwpVirtualKeyboardManagerV1);
 delegate_noop!(State: ZwpVirtualKeyboardV1);
@@ -0,0 +1,252 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use cosmic::iced::futures::{Stream, StreamExt, channel::mpsc};
+use cosmic_osk::{Message, cli::Request, dbus};
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+use zbus::{
+    MatchRule, MessageStream, Proxy, connection, interface,
+    zvariant::{OwnedObjectPath, OwnedValue},
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Private session bus, stopped when dropped
+struct Bus {
+    daemon: Child,
+    address: String,
+}
+
+impl Bus {
+    /// Fails the test if `dbus-daemon` is not installed, as skipping would
+    /// report a pass
+    fn start() -> Self {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("failed to run dbus-daemon, which these tests need");
+        let stdout = daemon.stdout.take().unwrap();
+        let mut bus = Self {
+            daemon,
+            address: String::new(),
+        };
+        BufReader::new(stdout)
+            .read_line(&mut bus.address)
+            .expect("failed to read dbus-daemon address");
+        bus.address = bus.address.trim().to_string();
+        assert!(!bus.address.is_empty(), "dbus-daemon printed no address");
+        bus
+    }
+
+    async fn connect(&self) -> zbus::Connection {
+        connection::Builder::address(self.address.as_str())
+            .unwrap()
+            .build()
+            .await
+            .unwrap()
+    }
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+/// Launcher of the accessibility bus, which is the private bus itself
+struct A11yBus {
+    address: String,
+}
+
+#[interface(name = "org.a11y.Bus")]
+impl A11yBus {
+    fn get_address(&self) -> String {
+        self.address.clone()
+    }
+}
+
+/// Accessibility registry, sending the applications embedded in it
+struct Registry {
+    embedded: mpsc::UnboundedSender<(String, OwnedObjectPath)>,
+}
+
+#[interface(name = "org.a11y.atspi.Socket")]
+impl Registry {
+    fn embed(&self, plug: (String, OwnedObjectPath)) -> (String, OwnedObjectPath) {
+        self.embedded.unbounded_send(plug).unwrap();
+        (
+            "org.a11y.atspi.Registry".to_string(),
+            OwnedObjectPath::try_from(ROOT_PATH).unwrap(),
+        )
+    }
+}
+
+const ROOT_PATH: &str = "/org/a11y/atspi/accessible/root";
+
+async fn next<T>(stream: &mut (impl Stream<Item = T> + Unpin)) -> T {
+    tokio::time::timeout(TIMEOUT, stream.next())
+        .await
+        .expect("timed out")
+        .expect("stream ended")
+}
+
+#[tokio::test]
+async fn show_hide_toggle_and_visible_property() {
+    let bus = Bus::start();
+    let (msg_tx, mut msg_rx) = mpsc::channel(16);
+    let builder = connection::Builder::address(bus.address.as_str()).unwrap();
+    tokio::spawn(async move {
+        dbus::serve(builder, msg_tx).await.unwrap();
+    });
+    let Message::DbusTx(update_tx) = next(&mut msg_rx).await else {
+        panic!("service did not send its update channel");
+    };
+
+    let client = bus.connect().await;
+    let osk = Proxy::new(&client, dbus::NAME, dbus::PATH, dbus::NAME)
+        .await
+        .unwrap();
+    let puri = Proxy::new(&client, dbus::PURI_NAME, dbus::PURI_PATH, dbus::PURI_NAME)
+        .await
+        .unwrap();
+
+    for (method, request) in [
+        ("Show", Request::Show),
+        ("Hide", Request::Hide),
+        ("Toggle", Request::Toggle),
+    ] {
+        let () = osk.call(method, &()).await.unwrap();
+        let message = next(&mut msg_rx).await;
+        assert!(
+            matches!(message, Message::Request(sent) if sent == request),
+            "{method} sent {message:?}"
+        );
+    }
+    let () = puri.call("SetVisible", &(false,)).await.unwrap();
+    assert!(matches!(
+        next(&mut msg_rx).await,
+        Message::Request(Request::Hide)
+    ));
+
+    assert!(!osk.get_property::<bool>("Visible").await.unwrap());
+    let mut visible_changed = osk.receive_property_changed::<bool>("Visible").await;
+    let mut visibility_changed = osk.receive_signal("VisibilityChanged").await.unwrap();
+    let mut puri_visible_changed = puri.receive_property_changed::<bool>("Visible").await;
+    dbus::update(
+        &update_tx,
+        dbus::Status {
+            visible: true,
+            layout: "full".to_string(),
+            height: 256,
+        },
+    );
+    assert!(next(&mut visible_changed).await.get().await.unwrap());
+    let signal = next(&mut visibility_changed).await;
+    assert_eq!(signal.body().deserialize::<(bool,)>().unwrap(), (true,));
+    assert!(next(&mut puri_visible_changed).await.get().await.unwrap());
+    assert_eq!(
+        osk.get_property::<String>("ActiveLayout").await.unwrap(),
+        "full"
+    );
+    assert_eq!(osk.get_property::<u32>("Height").await.unwrap(), 256);
+}
+
+#[tokio::test]
+async fn announcement_is_an_atspi_event() {
+    let bus = Bus::start();
+    let _launcher = connection::Builder::address(bus.address.as_str())
+        .unwrap()
+        .name("org.a11y.Bus")
+        .unwrap()
+        .serve_at(
+            "/org/a11y/bus",
+            A11yBus {
+                address: bus.address.clone(),
+            },
+        )
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    let (embedded_tx, mut embedded_rx) = mpsc::unbounded();
+    let _registry = connection::Builder::address(bus.address.as_str())
+        .unwrap()
+        .name("org.a11y.atspi.Registry")
+        .unwrap()
+        .serve_at(
+            ROOT_PATH,
+            Registry {
+                embedded: embedded_tx,
+            },
+        )
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    let client = bus.connect().await;
+    let rule = MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .interface("org.a11y.atspi.Event.Object")
+        .unwrap()
+        .member("Announcement")
+        .unwrap()
+        .build();
+    let mut announcements = MessageStream::for_match_rule(rule, &client, None)
+        .await
+        .unwrap();
+
+    let (msg_tx, mut msg_rx) = mpsc::channel(16);
+    let builder = connection::Builder::address(bus.address.as_str()).unwrap();
+    tokio::spawn(async move {
+        dbus::serve(builder, msg_tx).await.unwrap();
+    });
+    let Message::DbusTx(update_tx) = next(&mut msg_rx).await else {
+        panic!("service did not send its update channel");
+    };
+
+    // The keyboard is known to assistive technologies as an application
+    let (bus_name, path) = next(&mut embedded_rx).await;
+    assert_eq!(path.as_str(), ROOT_PATH);
+    let root: Proxy = zbus::proxy::Builder::new(&client)
+        .destination(bus_name.as_str())
+        .unwrap()
+        .path(ROOT_PATH)
+        .unwrap()
+        .interface("org.a11y.atspi.Accessible")
+        .unwrap()
+        .cache_properties(zbus::proxy::CacheProperties::No)
+        .build()
+        .await
+        .unwrap();
+    let role: u32 = root.call("GetRole", &()).await.unwrap();
+    assert_eq!(role, 75);
+    assert_eq!(
+        root.get_property::<String>("Name").await.unwrap(),
+        "COSMIC On-Screen Keyboard"
+    );
+
+    dbus::announce(&update_tx, "Symbols".to_string());
+    let message = next(&mut announcements).await.unwrap();
+    let header = message.header();
+    assert_eq!(header.path().unwrap().as_str(), ROOT_PATH);
+    assert_eq!(header.sender().unwrap().as_str(), bus_name);
+    // Announcements start once the keyboard is embedded
+    let (parent_name, _) = root
+        .get_property::<(String, OwnedObjectPath)>("Parent")
+        .await
+        .unwrap();
+    assert_eq!(parent_name, "org.a11y.atspi.Registry");
+    let (kind, politeness, _, text, _) = message
+        .body()
+        .deserialize::<(String, i32, i32, OwnedValue, HashMap<String, OwnedValue>)>()
+        .unwrap();
+    assert_eq!(kind, "");
+    assert_eq!(politeness, 1);
+    assert_eq!(String::try_from(text).unwrap(), "Symbols");
+}